@@ -38,6 +38,11 @@ cd roc_nightly
 # build the basic cli platform
 ./roc build ../basic-cli/examples/countdown.roc --optimize
 
+# `roc build` links a freshly rebuilt platform directly, so the surgical linker's .rh and .rm files are produced here
+if [ "$(uname -s)" == "Linux" ] && [ "$(uname -m)" == "x86_64" ]; then
+    ./roc preprocess-host ../basic-cli/examples/countdown.roc
+fi
+
 # We need this extra variable so we can safely check if $2 is empty later
 EXTRA_ARGS=${2:-}
 
@@ -5,6 +5,7 @@ use std::path::{Path, PathBuf};
 use bumpalo::Bump;
 use roc_error_macros::{internal_error, user_error};
 use roc_fmt::def::fmt_defs;
+use roc_fmt::expr::MultilineCache;
 use roc_fmt::module::fmt_module;
 use roc_fmt::spaces::RemoveSpaces;
 use roc_fmt::{Ast, Buf};
@@ -234,6 +235,10 @@ fn parse_all<'a>(arena: &'a Bump, src: &'a str) -> Result<Ast<'a>, SyntaxError<'
 }
 
 fn fmt_all<'a>(buf: &mut Buf<'a>, ast: &'a Ast) {
+    // The cache is dropped before this returns, so `ast` outlives every address in it,
+    // and the cache never sees another AST.
+    let _multiline_cache = MultilineCache::enable();
+
     fmt_module(buf, &ast.module);
 
     fmt_defs(buf, &ast.defs, 0);
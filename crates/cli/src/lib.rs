@@ -67,7 +67,11 @@ pub const FLAG_CHECK: &str = "check";
 pub const FLAG_STDIN: &str = "stdin";
 pub const FLAG_STDOUT: &str = "stdout";
 pub const FLAG_WASM_STACK_SIZE_KB: &str = "wasm-stack-size-kb";
+pub const FLAG_MAX_SIZE: &str = "max-size";
+pub const FLAG_STRIP_SECTIONS: &str = "strip-sections";
 pub const FLAG_OUTPUT: &str = "output";
+pub const FLAG_DRY_RUN: &str = "dry-run";
+pub const FLAG_APP_FUNCTION: &str = "app-function";
 pub const ROC_FILE: &str = "ROC_FILE";
 pub const ROC_DIR: &str = "ROC_DIR";
 pub const GLUE_DIR: &str = "GLUE_DIR";
@@ -123,7 +127,7 @@ pub fn build_app() -> Command {
 
     let flag_linker = Arg::new(FLAG_LINKER)
         .long(FLAG_LINKER)
-        .help("Set which linker to use\n(The surgical linker is enabled by default only when building for wasm32 or x86_64 Linux, because those are the only targets it currently supports. Otherwise the legacy linker is used by default.)")
+        .help("Set which linker to use\n(The surgical linker is enabled by default only when building for wasm32 or x86_64 Linux, because those are the only targets it currently supports. Otherwise the legacy linker is used by default. AArch64 Linux can use the surgical linker with `--linker surgical`.)")
         .value_parser(["surgical", "legacy"])
         .required(false);
 
@@ -139,6 +143,18 @@ pub fn build_app() -> Command {
         .value_parser(value_parser!(u32))
         .required(false);
 
+    let flag_max_size = Arg::new(FLAG_MAX_SIZE)
+        .long(FLAG_MAX_SIZE)
+        .help("Fail the build if the linked executable is bigger than this many bytes\n(This only applies to the surgical linker.)")
+        .value_parser(value_parser!(u64))
+        .required(false);
+
+    let flag_strip_sections = Arg::new(FLAG_STRIP_SECTIONS)
+        .long(FLAG_STRIP_SECTIONS)
+        .help("Leave the section header table out of the linked executable to make it smaller\n(This only applies to the surgical linker on ELF hosts.)")
+        .action(ArgAction::SetTrue)
+        .required(false);
+
     let roc_file_to_run = Arg::new(ROC_FILE)
         .help("The .roc file of an app to run")
         .value_parser(value_parser!(PathBuf))
@@ -176,6 +192,8 @@ pub fn build_app() -> Command {
             .arg(flag_linker.clone())
             .arg(flag_prebuilt.clone())
             .arg(flag_wasm_stack_size_kb)
+            .arg(flag_max_size)
+            .arg(flag_strip_sections)
             .arg(
                 Arg::new(FLAG_TARGET)
                     .long(FLAG_TARGET)
@@ -382,6 +400,20 @@ pub fn build_app() -> Command {
                     .value_parser(build_target_values_parser)
                     .required(false),
             )
+            .arg(
+                Arg::new(FLAG_DRY_RUN)
+                    .long(FLAG_DRY_RUN)
+                    .help("Only analyze the host, without writing the preprocessed host or its metadata")
+                    .action(ArgAction::SetTrue)
+                    .required(false),
+            )
+            .arg(
+                Arg::new(FLAG_APP_FUNCTION)
+                    .long(FLAG_APP_FUNCTION)
+                    .help("Treat this host import as an app function even though it has no `roc_` prefix\n(can be given multiple times)")
+                    .action(ArgAction::Append)
+                    .required(false),
+            )
         )
         .arg(flag_optimize)
         .arg(flag_max_threads)
@@ -726,14 +758,17 @@ pub fn build(
 
     let wasm_dev_backend = matches!(code_gen_backend, CodeGenBackend::Wasm);
 
+    let linker = matches.get_one::<String>(FLAG_LINKER).map(|s| s.as_str());
     let linking_strategy = if wasm_dev_backend {
         LinkingStrategy::Additive
-    } else if !roc_linker::supported(link_type, &triple)
-        || matches.get_one::<String>(FLAG_LINKER).map(|s| s.as_str()) == Some("legacy")
-    {
+    } else if linker == Some("legacy") {
         LinkingStrategy::Legacy
-    } else {
+    } else if roc_linker::supported(link_type, &triple)
+        || (linker == Some("surgical") && roc_linker::supported_when_requested(link_type, &triple))
+    {
         LinkingStrategy::Surgical
+    } else {
+        LinkingStrategy::Legacy
     };
 
     let prebuilt = {
@@ -754,6 +789,19 @@ pub fn build(
         .flatten()
         .map(|x| x * 1024);
 
+    let max_size: Option<u64> = matches
+        .try_get_one::<u64>(FLAG_MAX_SIZE)
+        .ok()
+        .flatten()
+        .copied();
+
+    let strip_sections = matches
+        .try_get_one::<bool>(FLAG_STRIP_SECTIONS)
+        .ok()
+        .flatten()
+        .copied()
+        .unwrap_or(false);
+
     let build_ordering = match config {
         BuildAndRunIfNoErrors => BuildOrdering::BuildIfChecks,
         _ => BuildOrdering::AlwaysBuild,
@@ -778,6 +826,8 @@ pub fn build(
         linking_strategy,
         prebuilt,
         wasm_dev_stack_bytes,
+        max_size,
+        strip_sections,
         roc_cache_dir,
         load_config,
         out_path,
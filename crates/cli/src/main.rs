@@ -5,9 +5,9 @@ use roc_build::program::{check_file, CodeGenBackend};
 use roc_cli::{
     build_app, format_files, format_src, test, BuildConfig, FormatMode, CMD_BUILD, CMD_CHECK,
     CMD_DEV, CMD_DOCS, CMD_FORMAT, CMD_GEN_STUB_LIB, CMD_GLUE, CMD_PREPROCESS_HOST, CMD_REPL,
-    CMD_RUN, CMD_TEST, CMD_VERSION, DIRECTORY_OR_FILES, FLAG_CHECK, FLAG_DEV, FLAG_LIB,
-    FLAG_NO_LINK, FLAG_OUTPUT, FLAG_STDIN, FLAG_STDOUT, FLAG_TARGET, FLAG_TIME, GLUE_DIR,
-    GLUE_SPEC, ROC_FILE,
+    CMD_RUN, CMD_TEST, CMD_VERSION, DIRECTORY_OR_FILES, FLAG_APP_FUNCTION, FLAG_CHECK, FLAG_DEV,
+    FLAG_DRY_RUN, FLAG_LIB, FLAG_NO_LINK, FLAG_OUTPUT, FLAG_STDIN, FLAG_STDOUT, FLAG_TARGET,
+    FLAG_TIME, GLUE_DIR, GLUE_SPEC, ROC_FILE,
 };
 use roc_docs::generate_docs_html;
 use roc_error_macros::user_error;
@@ -151,6 +151,11 @@ fn main() -> io::Result<()> {
                 function_kind,
             );
 
+            let app_function_names: Vec<String> = matches
+                .get_many::<String>(FLAG_APP_FUNCTION)
+                .map(|names| names.cloned().collect())
+                .unwrap_or_default();
+
             // TODO: pipeline the executable location through here.
             // Currently it is essentally hardcoded as platform_path/dynhost.
             roc_linker::preprocess_host(
@@ -162,6 +167,8 @@ fn main() -> io::Result<()> {
                     .with_file_name(format!("{}.rh", get_target_triple_str(&triple).unwrap())),
                 &stub_lib,
                 &stub_dll_symbols,
+                &app_function_names,
+                matches.get_flag(FLAG_DRY_RUN),
             );
             Ok(0)
         }
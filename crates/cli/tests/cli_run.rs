@@ -16,7 +16,9 @@ mod cli_run {
     };
     use const_format::concatcp;
     use indoc::indoc;
-    use roc_cli::{CMD_BUILD, CMD_CHECK, CMD_DEV, CMD_FORMAT, CMD_RUN, CMD_TEST};
+    use roc_cli::{
+        CMD_BUILD, CMD_CHECK, CMD_DEV, CMD_FORMAT, CMD_PREPROCESS_HOST, CMD_RUN, CMD_TEST,
+    };
     use roc_test_utils::assert_multiline_str_eq;
     use serial_test::serial;
     use std::iter;
@@ -1025,6 +1027,14 @@ mod cli_run {
                     TestCliCommands::Run,
                 );
 
+                // Rebuilding the platform links it with the surgical linker directly, without
+                // writing a preprocessed host, so we preprocess it here for the runs below.
+                #[cfg(all(target_os = "linux", target_arch = "x86_64"))]
+                {
+                    let out = run_roc([CMD_PREPROCESS_HOST, file_name.to_str().unwrap()], &[], &[]);
+                    assert!(out.status.success(), "{}", out.stderr);
+                }
+
                 ran_without_optimizations = true;
             });
 
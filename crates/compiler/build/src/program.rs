@@ -3,7 +3,7 @@ use crate::link::{
 };
 use bumpalo::Bump;
 use inkwell::memory_buffer::MemoryBuffer;
-use roc_error_macros::internal_error;
+use roc_error_macros::{internal_error, user_error};
 use roc_gen_dev::AssemblyBackendMode;
 use roc_gen_llvm::llvm::build::{module_from_builtins, LlvmBackendMode};
 use roc_gen_llvm::llvm::externs::add_default_roc_externs;
@@ -726,6 +726,8 @@ pub fn build_file<'a>(
     linking_strategy: LinkingStrategy,
     prebuilt_requested: bool,
     wasm_dev_stack_bytes: Option<u32>,
+    max_size: Option<u64>,
+    strip_sections: bool,
     roc_cache_dir: RocCacheDir<'_>,
     load_config: LoadConfig,
     out_path: Option<&Path>,
@@ -747,6 +749,8 @@ pub fn build_file<'a>(
         linking_strategy,
         prebuilt_requested,
         wasm_dev_stack_bytes,
+        max_size,
+        strip_sections,
         loaded,
         compilation_start,
         out_path,
@@ -764,6 +768,8 @@ fn build_loaded_file<'a>(
     mut linking_strategy: LinkingStrategy,
     prebuilt_requested: bool,
     wasm_dev_stack_bytes: Option<u32>,
+    max_size: Option<u64>,
+    strip_sections: bool,
     loaded: roc_load::MonomorphizedModule<'a>,
     compilation_start: Instant,
     out_path: Option<&Path>,
@@ -871,7 +877,6 @@ fn build_loaded_file<'a>(
             linking_strategy,
             platform_main_roc.clone(),
             preprocessed_host_path.clone(),
-            output_exe_path.clone(),
             target,
             dll_stub_symbols,
         );
@@ -912,12 +917,12 @@ fn build_loaded_file<'a>(
 
     enum HostRebuildTiming {
         BeforeApp(u128),
-        ConcurrentWithApp(JoinHandle<u128>),
+        ConcurrentWithApp(JoinHandle<(u128, Option<RebuiltHost>)>),
     }
 
     let opt_rebuild_timing = if let Some(rebuild_thread) = rebuild_thread {
         if linking_strategy == LinkingStrategy::Additive {
-            let rebuild_duration = rebuild_thread
+            let (rebuild_duration, _) = rebuild_thread
                 .join()
                 .expect("Failed to (re)build platform.");
 
@@ -972,8 +977,11 @@ fn build_loaded_file<'a>(
         );
     }
 
+    let mut opt_rebuilt_host = None;
     if let Some(HostRebuildTiming::ConcurrentWithApp(thread)) = opt_rebuild_timing {
-        let rebuild_duration = thread.join().expect("Failed to (re)build platform.");
+        let (rebuild_duration, rebuilt_host) =
+            thread.join().expect("Failed to (re)build platform.");
+        opt_rebuilt_host = rebuilt_host;
 
         if emit_timings && !is_platform_prebuilt {
             println!("Finished rebuilding the platform in {rebuild_duration} ms\n");
@@ -984,14 +992,37 @@ fn build_loaded_file<'a>(
     let link_start = Instant::now();
 
     match (linking_strategy, link_type) {
-        (LinkingStrategy::Surgical, _) => {
-            roc_linker::link_preprocessed_host(
-                target,
-                &platform_main_roc,
-                &roc_app_bytes,
-                &output_exe_path,
-            );
-        }
+        (LinkingStrategy::Surgical, _) => match opt_rebuilt_host {
+            Some(rebuilt_host) => {
+                // The host was just rebuilt, so it is preprocessed and linked in one go,
+                // without writing a preprocessed host to disk.
+                roc_linker::link(
+                    target,
+                    &rebuilt_host.host_path,
+                    &rebuilt_host.stub_lib_path,
+                    &rebuilt_host.stub_dll_symbols,
+                    &[],
+                    &roc_app_bytes,
+                    &output_exe_path,
+                    max_size,
+                    strip_sections,
+                )
+                .unwrap_or_else(|e| {
+                    user_error!("Failed to link the app into the rebuilt platform: {}", e)
+                });
+            }
+            None => {
+                roc_linker::link_preprocessed_host(
+                    target,
+                    &platform_main_roc,
+                    &roc_app_bytes,
+                    &output_exe_path,
+                    max_size,
+                    strip_sections,
+                )
+                .unwrap_or_else(|e| user_error!("Failed to link the app: {}", e));
+            }
+        },
         (LinkingStrategy::Additive, _) | (LinkingStrategy::Legacy, LinkType::None) => {
             // Just copy the object file to the output folder.
             output_exe_path.set_extension(operating_system.object_file_ext());
@@ -1097,16 +1128,21 @@ fn invalid_prebuilt_platform(prebuilt_requested: bool, preprocessed_host_path: P
     );
 }
 
-#[allow(clippy::too_many_arguments)]
+/// A host that was rebuilt for surgical linking, along with the stub library it was built against.
+struct RebuiltHost {
+    host_path: PathBuf,
+    stub_lib_path: PathBuf,
+    stub_dll_symbols: Vec<String>,
+}
+
 fn spawn_rebuild_thread(
     opt_level: OptLevel,
     linking_strategy: LinkingStrategy,
     platform_main_roc: PathBuf,
     preprocessed_host_path: PathBuf,
-    output_exe_path: PathBuf,
     target: &Triple,
     dll_stub_symbols: Vec<String>,
-) -> std::thread::JoinHandle<u128> {
+) -> std::thread::JoinHandle<(u128, Option<RebuiltHost>)> {
     let thread_local_target = target.clone();
     std::thread::spawn(move || {
         // Printing to stderr because we want stdout to contain only the output of the roc program.
@@ -1116,7 +1152,7 @@ fn spawn_rebuild_thread(
 
         let rebuild_host_start = Instant::now();
 
-        match linking_strategy {
+        let rebuilt_host = match linking_strategy {
             LinkingStrategy::Additive => {
                 let host_dest = rebuild_host(
                     opt_level,
@@ -1126,19 +1162,25 @@ fn spawn_rebuild_thread(
                 );
 
                 preprocess_host_wasm32(host_dest.as_path(), &preprocessed_host_path);
+
+                None
             }
             LinkingStrategy::Surgical => {
-                build_and_preprocess_host_lowlevel(
+                // The rebuilt host is preprocessed and linked in one go by `roc_linker::link`,
+                // so no preprocessed host is written here. Use `roc preprocess-host` to get one
+                // for `--prebuilt-platform` builds.
+                let (host_path, stub_lib_path) = rebuild_host_with_stub_lib(
                     opt_level,
                     &thread_local_target,
                     platform_main_roc.as_path(),
-                    preprocessed_host_path.as_path(),
                     &dll_stub_symbols,
                 );
 
-                // Copy preprocessed host to executable location.
-                // The surgical linker will modify that copy in-place.
-                std::fs::copy(&preprocessed_host_path, output_exe_path.as_path()).unwrap();
+                Some(RebuiltHost {
+                    host_path,
+                    stub_lib_path,
+                    stub_dll_symbols: dll_stub_symbols,
+                })
             }
             LinkingStrategy::Legacy => {
                 rebuild_host(
@@ -1147,10 +1189,12 @@ fn spawn_rebuild_thread(
                     platform_main_roc.as_path(),
                     None,
                 );
+
+                None
             }
-        }
+        };
 
-        rebuild_host_start.elapsed().as_millis()
+        (rebuild_host_start.elapsed().as_millis(), rebuilt_host)
     })
 }
 
@@ -1163,36 +1207,36 @@ pub fn build_and_preprocess_host(
 ) {
     let stub_dll_symbols = exposed_symbols.stub_dll_symbols();
 
-    build_and_preprocess_host_lowlevel(
-        opt_level,
+    let (_, stub_lib) =
+        rebuild_host_with_stub_lib(opt_level, target, platform_main_roc, &stub_dll_symbols);
+
+    roc_linker::preprocess_host(
         target,
         platform_main_roc,
         preprocessed_host_path,
+        &stub_lib,
         &stub_dll_symbols,
-    )
+        &[],
+        false,
+    );
 }
 
-fn build_and_preprocess_host_lowlevel(
+/// Rebuilds the host against a stub library of the app.
+/// Returns the paths of the rebuilt host and of the stub library.
+fn rebuild_host_with_stub_lib(
     opt_level: OptLevel,
     target: &Triple,
     platform_main_roc: &Path,
-    preprocessed_host_path: &Path,
     stub_dll_symbols: &[String],
-) {
+) -> (PathBuf, PathBuf) {
     let stub_lib =
         roc_linker::generate_stub_lib_from_loaded(target, platform_main_roc, stub_dll_symbols);
 
     debug_assert!(stub_lib.exists());
 
-    rebuild_host(opt_level, target, platform_main_roc, Some(&stub_lib));
+    let host_path = rebuild_host(opt_level, target, platform_main_roc, Some(&stub_lib));
 
-    roc_linker::preprocess_host(
-        target,
-        platform_main_roc,
-        preprocessed_host_path,
-        &stub_lib,
-        stub_dll_symbols,
-    )
+    (host_path, stub_lib)
 }
 
 #[allow(clippy::too_many_arguments)]
@@ -1318,6 +1362,7 @@ pub fn build_str_test<'a>(
         linking_strategy,
         assume_prebuild,
         wasm_dev_stack_bytes,
+        None,
         loaded,
         compilation_start,
         None,
@@ -1401,13 +1401,13 @@ hashBytesHelper16 = \seed, list, index, remaining ->
         hashBytesHelper16 newSeed list newIndex newRemaining
 
 wyp0 : U64
-wyp0 = 0xa0761d6478bd642f
+wyp0 = 0xA0761D6478BD642F
 wyp1 : U64
-wyp1 = 0xe7037ed1a0b428db
+wyp1 = 0xE7037ED1A0B428DB
 wyp2 : U64
-wyp2 = 0x8ebc6af09c88c6e3
+wyp2 = 0x8EBC6AF09C88C6E3
 wyp3 : U64
-wyp3 = 0x589965cc75374cc3
+wyp3 = 0x589965CC75374CC3
 
 wymix : U64, U64 -> U64
 wymix = \a, b ->
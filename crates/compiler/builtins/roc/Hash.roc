@@ -103,7 +103,7 @@ hashNat : a, Nat -> a where a implements Hasher
 hashNat = \hasher, n ->
     isPlatform32bit =
         x : Nat
-        x = 0xffff_ffff
+        x = 0xFFFF_FFFF
         y = Num.addWrap x 1
 
         y == 0
@@ -196,12 +196,12 @@ encodeStrBytes = \str ->
         List.walkUntil bytes initialState \{ bytePos, status }, b ->
             when b is
                 0x22 -> Break { bytePos, status: FoundEscape } # U+0022 Quotation mark
-                0x5c -> Break { bytePos, status: FoundEscape } # U+005c Reverse solidus
-                0x2f -> Break { bytePos, status: FoundEscape } # U+002f Solidus
+                0x5C -> Break { bytePos, status: FoundEscape } # U+005c Reverse solidus
+                0x2F -> Break { bytePos, status: FoundEscape } # U+002f Solidus
                 0x08 -> Break { bytePos, status: FoundEscape } # U+0008 Backspace
-                0x0c -> Break { bytePos, status: FoundEscape } # U+000c Form feed
-                0x0a -> Break { bytePos, status: FoundEscape } # U+000a Line feed
-                0x0d -> Break { bytePos, status: FoundEscape } # U+000d Carriage return
+                0x0C -> Break { bytePos, status: FoundEscape } # U+000c Form feed
+                0x0A -> Break { bytePos, status: FoundEscape } # U+000a Line feed
+                0x0D -> Break { bytePos, status: FoundEscape } # U+000d Carriage return
                 0x09 -> Break { bytePos, status: FoundEscape } # U+0009 Tab
                 _ -> Continue { bytePos: bytePos + 1, status }
 
@@ -238,14 +238,14 @@ encodeStrBytes = \str ->
 escapedByteToJson : U8 -> List U8
 escapedByteToJson = \b ->
     when b is
-        0x22 -> [0x5c, 0x22] # U+0022 Quotation mark
-        0x5c -> [0x5c, 0x5c] # U+005c Reverse solidus
-        0x2f -> [0x5c, 0x2f] # U+002f Solidus
-        0x08 -> [0x5c, 'b'] # U+0008 Backspace
-        0x0c -> [0x5c, 'f'] # U+000c Form feed
-        0x0a -> [0x5c, 'n'] # U+000a Line feed
-        0x0d -> [0x5c, 'r'] # U+000d Carriage return
-        0x09 -> [0x5c, 'r'] # U+0009 Tab
+        0x22 -> [0x5C, 0x22] # U+0022 Quotation mark
+        0x5C -> [0x5C, 0x5C] # U+005c Reverse solidus
+        0x2F -> [0x5C, 0x2F] # U+002f Solidus
+        0x08 -> [0x5C, 'b'] # U+0008 Backspace
+        0x0C -> [0x5C, 'f'] # U+000c Form feed
+        0x0A -> [0x5C, 'n'] # U+000a Line feed
+        0x0D -> [0x5C, 'r'] # U+000d Carriage return
+        0x09 -> [0x5C, 'r'] # U+0009 Tab
         _ -> [b]
 
 expect escapedByteToJson '\n' == ['\\', 'n']
@@ -1030,12 +1030,12 @@ escapedCharFromJson : U8 -> U8
 escapedCharFromJson = \b ->
     when b is
         '"' -> 0x22 # U+0022 Quotation mark
-        '\\' -> 0x5c # U+005c Reverse solidus
-        '/' -> 0x2f # U+002f Solidus
+        '\\' -> 0x5C # U+005c Reverse solidus
+        '/' -> 0x2F # U+002f Solidus
         'b' -> 0x08 # U+0008 Backspace
-        'f' -> 0x0c # U+000c Form feed
-        'n' -> 0x0a # U+000a Line feed
-        'r' -> 0x0d # U+000d Carriage return
+        'f' -> 0x0C # U+000c Form feed
+        'n' -> 0x0A # U+000a Line feed
+        'r' -> 0x0D # U+000d Carriage return
         't' -> 0x09 # U+0009 Tab
         _ -> b
 
@@ -1285,7 +1285,7 @@ isWhitespace = \b ->
         _ -> Bool.false
 
 expect
-    input = ['1', 'a', ' ', '\n', 0x0d, 0x09]
+    input = ['1', 'a', ' ', '\n', 0x0D, 0x09]
     actual = List.map input isWhitespace
     expected = [Bool.false, Bool.false, Bool.true, Bool.true, Bool.true, Bool.true]
 
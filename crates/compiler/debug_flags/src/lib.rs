@@ -158,6 +158,12 @@ flags! {
     /// Prints LLVM function verification output.
     ROC_PRINT_LLVM_FN_VERIFICATION
 
+    // ===Dev Gen===
+
+    /// Prints the peak general and float register pressure of every procedure built by the dev
+    /// backend, to see how close it came to spilling.
+    ROC_PRINT_DEV_REGISTER_PRESSURE
+
     // ===WASM Gen===
 
     /// Writes a `final.wasm` file to /tmp
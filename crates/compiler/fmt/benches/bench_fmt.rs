@@ -0,0 +1,54 @@
+use bumpalo::Bump;
+use criterion::{black_box, criterion_group, criterion_main, Criterion};
+use roc_fmt::annotation::Formattable;
+use roc_fmt::expr::MultilineCache;
+use roc_fmt::Buf;
+use roc_parse::test_helpers::parse_expr_with;
+
+/// About 100 nodes per level, so a depth of 100 gives an expression of roughly 10k nodes.
+fn nested_expr_src(depth: usize) -> String {
+    let args = (0..48)
+        .map(|i| format!("x{i}"))
+        .collect::<Vec<_>>()
+        .join(" ");
+
+    let mut src = String::from("x");
+
+    for _ in 0..depth {
+        src = format!("if c then f {args} ({src}) else g {args}");
+    }
+
+    src
+}
+
+pub fn fmt_benchmark(c: &mut Criterion) {
+    let src = nested_expr_src(100);
+    let arena = Bump::new();
+    let expr = parse_expr_with(&arena, &src).unwrap();
+
+    c.bench_function("format nested expr", |b| {
+        b.iter(|| {
+            let buf_arena = Bump::new();
+            let mut buf = Buf::new_in(&buf_arena);
+            let _multiline_cache = MultilineCache::enable();
+
+            expr.format(&mut buf, 0);
+
+            black_box(buf.as_str().len());
+        })
+    });
+
+    c.bench_function("format nested expr without multiline cache", |b| {
+        b.iter(|| {
+            let buf_arena = Bump::new();
+            let mut buf = Buf::new_in(&buf_arena);
+
+            expr.format(&mut buf, 0);
+
+            black_box(buf.as_str().len());
+        })
+    });
+}
+
+criterion_group!(benches, fmt_benchmark);
+criterion_main!(benches);
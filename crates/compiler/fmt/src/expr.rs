@@ -7,6 +7,7 @@ use crate::spaces::{
     INDENT,
 };
 use crate::Buf;
+use roc_collections::MutMap;
 use roc_module::called_via::{self, BinOp};
 use roc_parse::ast::{
     AssignedField, Base, Collection, CommentOrNewline, Expr, ExtractSpaces, Pattern,
@@ -15,12 +16,73 @@ use roc_parse::ast::{
 use roc_parse::ast::{StrLiteral, StrSegment};
 use roc_parse::ident::Accessor;
 use roc_region::all::Loc;
+use std::cell::RefCell;
+
+std::thread_local! {
+    /// Answers to `is_multiline` for subexpressions, keyed by address.
+    /// Only populated while a `MultilineCache` is alive.
+    static MULTILINE_CACHE: RefCell<Option<MutMap<usize, bool>>> = RefCell::new(None);
+}
+
+/// While this guard is alive, `is_multiline` answers for subexpressions are remembered,
+/// so formatting a deeply nested expression doesn't re-walk the same subtrees at every level.
+///
+/// Answers are keyed by address, so the ASTs formatted while the guard is alive
+/// must not be deallocated before it is dropped. Every guard starts with an empty cache,
+/// and puts back the cache of any enclosing guard when it is dropped.
+pub struct MultilineCache {
+    outer: Option<MutMap<usize, bool>>,
+}
+
+impl MultilineCache {
+    pub fn enable() -> Self {
+        let outer = MULTILINE_CACHE.with(|cache| cache.borrow_mut().replace(MutMap::default()));
+
+        MultilineCache { outer }
+    }
+}
+
+impl Drop for MultilineCache {
+    fn drop(&mut self) {
+        let outer = self.outer.take();
+
+        MULTILINE_CACHE.with(|cache| *cache.borrow_mut() = outer);
+    }
+}
+
+/// `is_multiline` for a subexpression that lives behind a reference in the AST.
+/// Never call this on a temporary, since its address could later be reused by another expression.
+fn is_sub_expr_multiline(expr: &Expr<'_>) -> bool {
+    let key = expr as *const Expr<'_> as usize;
+
+    let cached = MULTILINE_CACHE.with(|cache| {
+        cache
+            .borrow()
+            .as_ref()
+            .map(|answers| answers.get(&key).copied())
+    });
+
+    match cached {
+        // The cache is disabled
+        None => expr.is_multiline(),
+        Some(Some(answer)) => answer,
+        Some(None) => {
+            let answer = expr.is_multiline();
+
+            MULTILINE_CACHE.with(|cache| {
+                if let Some(answers) = cache.borrow_mut().as_mut() {
+                    answers.insert(key, answer);
+                }
+            });
+
+            answer
+        }
+    }
+}
 
 impl<'a> Formattable for Expr<'a> {
     fn is_multiline(&self) -> bool {
         use roc_parse::ast::Expr::*;
-        // TODO cache these answers using a Map<Pointer, bool>, so
-        // we don't have to traverse subexpressions repeatedly
 
         match self {
             // Return whether these spaces contain any Newlines
@@ -55,27 +117,33 @@ impl<'a> Formattable for Expr<'a> {
 
             Str(literal) => is_str_multiline(literal),
             Apply(loc_expr, args, _) => {
-                loc_expr.is_multiline() || args.iter().any(|loc_arg| loc_arg.is_multiline())
+                is_sub_expr_multiline(&loc_expr.value)
+                    || args
+                        .iter()
+                        .any(|loc_arg| is_sub_expr_multiline(&loc_arg.value))
             }
 
             Expect(condition, continuation) => {
-                condition.is_multiline() || continuation.is_multiline()
+                is_sub_expr_multiline(&condition.value)
+                    || is_sub_expr_multiline(&continuation.value)
             }
-            Dbg(condition, _) => condition.is_multiline(),
+            Dbg(condition, _) => is_sub_expr_multiline(&condition.value),
             LowLevelDbg(_, _, _) => unreachable!(
                 "LowLevelDbg should only exist after desugaring, not during formatting"
             ),
 
             If(branches, final_else) => {
-                final_else.is_multiline()
-                    || branches
-                        .iter()
-                        .any(|(c, t)| c.is_multiline() || t.is_multiline())
+                is_sub_expr_multiline(&final_else.value)
+                    || branches.iter().any(|(c, t)| {
+                        is_sub_expr_multiline(&c.value) || is_sub_expr_multiline(&t.value)
+                    })
             }
 
             BinOps(lefts, loc_right) => {
-                lefts.iter().any(|(loc_expr, _)| loc_expr.is_multiline())
-                    || loc_right.is_multiline()
+                lefts
+                    .iter()
+                    .any(|(loc_expr, _)| is_sub_expr_multiline(&loc_expr.value))
+                    || is_sub_expr_multiline(&loc_right.value)
             }
 
             UnaryOp(loc_subexpr, _)
@@ -83,21 +151,21 @@ impl<'a> Formattable for Expr<'a> {
                 expr: loc_subexpr, ..
             })
             | MultipleRecordBuilders(loc_subexpr)
-            | UnappliedRecordBuilder(loc_subexpr) => loc_subexpr.is_multiline(),
+            | UnappliedRecordBuilder(loc_subexpr) => is_sub_expr_multiline(&loc_subexpr.value),
 
-            ParensAround(subexpr) => subexpr.is_multiline(),
+            ParensAround(subexpr) => is_sub_expr_multiline(subexpr),
 
             Closure(loc_patterns, loc_body) => {
                 // check the body first because it's more likely to be multiline
-                loc_body.is_multiline()
+                is_sub_expr_multiline(&loc_body.value)
                     || loc_patterns
                         .iter()
                         .any(|loc_pattern| loc_pattern.is_multiline())
             }
             Backpassing(loc_patterns, loc_body, loc_ret) => {
                 // check the body first because it's more likely to be multiline
-                loc_body.is_multiline()
-                    || loc_ret.is_multiline()
+                is_sub_expr_multiline(&loc_body.value)
+                    || is_sub_expr_multiline(&loc_ret.value)
                     || loc_patterns
                         .iter()
                         .any(|loc_pattern| loc_pattern.is_multiline())
@@ -337,18 +405,7 @@ impl<'a> Formattable for Expr<'a> {
                 is_negative,
             } => {
                 buf.indent(indent);
-                if is_negative {
-                    buf.push('-');
-                }
-
-                match base {
-                    Base::Hex => buf.push_str("0x"),
-                    Base::Octal => buf.push_str("0o"),
-                    Base::Binary => buf.push_str("0b"),
-                    Base::Decimal => { /* nothing */ }
-                }
-
-                buf.push_str(string);
+                format_non_base10_int(buf, base, string, is_negative);
             }
             Record(fields) => {
                 fmt_record_like(
@@ -450,7 +507,7 @@ impl<'a> Formattable for Expr<'a> {
             Par(items) => {
                 buf.indent(indent);
                 buf.push_str("par ");
-                fmt_collection(buf, 0, Braces::Round, *items, Newlines::No);
+                fmt_collection(buf, indent, Braces::Round, *items, Newlines::No);
             }
             List(items) => fmt_collection(buf, indent, Braces::Square, *items, Newlines::No),
             BinOps(lefts, right) => fmt_binops(buf, lefts, right, false, indent),
@@ -546,6 +603,32 @@ fn needs_unicode_escape(ch: char) -> bool {
     matches!(ch, '\u{0000}'..='\u{001f}' | '\u{007f}'..='\u{009f}')
 }
 
+/// Hex digits are always written in uppercase (`0xFF`), so the same number is spelled the same
+/// way everywhere and the digits stand out from the lowercase `0x` prefix. The `0x`, `0o` and
+/// `0b` prefixes stay lowercase, since those are the only spellings the parser accepts, and `_`
+/// digit separators are kept exactly where they were.
+pub(crate) fn format_non_base10_int(buf: &mut Buf, base: Base, string: &str, is_negative: bool) {
+    if is_negative {
+        buf.push('-');
+    }
+
+    match base {
+        Base::Hex => {
+            buf.push_str("0x");
+            buf.push_str(&string.to_ascii_uppercase());
+        }
+        Base::Octal => {
+            buf.push_str("0o");
+            buf.push_str(string);
+        }
+        Base::Binary => {
+            buf.push_str("0b");
+            buf.push_str(string);
+        }
+        Base::Decimal => buf.push_str(string),
+    }
+}
+
 pub(crate) fn format_sq_literal(buf: &mut Buf, s: &str) {
     buf.push('\'');
     for c in s.chars() {
@@ -1030,6 +1113,9 @@ fn fmt_if<'a>(
         buf.indent(indent);
 
         if i > 0 {
+            if !is_multiline {
+                buf.spaces(1);
+            }
             buf.push_str("else");
             buf.spaces(1);
         }
@@ -1137,7 +1223,6 @@ fn fmt_if<'a>(
                 }
             }
         } else {
-            buf.push_str("");
             buf.spaces(1);
             loc_then.format(buf, return_indent);
         }
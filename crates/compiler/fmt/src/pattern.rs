@@ -1,8 +1,8 @@
 use crate::annotation::{Formattable, Newlines, Parens};
-use crate::expr::{fmt_str_literal, format_sq_literal};
+use crate::expr::{fmt_str_literal, format_non_base10_int, format_sq_literal};
 use crate::spaces::{fmt_comments_only, fmt_spaces, NewlineAt, INDENT};
 use crate::Buf;
-use roc_parse::ast::{Base, CommentOrNewline, Pattern, PatternAs};
+use roc_parse::ast::{CommentOrNewline, Pattern, PatternAs};
 
 pub fn fmt_pattern<'a>(buf: &mut Buf, pattern: &'a Pattern<'a>, indent: u16, parens: Parens) {
     pattern.format_with_options(buf, parens, Newlines::No, indent);
@@ -160,18 +160,7 @@ impl<'a> Formattable for Pattern<'a> {
                 is_negative,
             } => {
                 buf.indent(indent);
-                if is_negative {
-                    buf.push('-');
-                }
-
-                match base {
-                    Base::Hex => buf.push_str("0x"),
-                    Base::Octal => buf.push_str("0o"),
-                    Base::Binary => buf.push_str("0b"),
-                    Base::Decimal => { /* nothing */ }
-                }
-
-                buf.push_str(string);
+                format_non_base10_int(buf, base, string, is_negative);
             }
             &FloatLiteral(string) => {
                 buf.indent(indent);
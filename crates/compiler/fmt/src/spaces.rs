@@ -3,7 +3,7 @@ use bumpalo::Bump;
 use roc_module::called_via::{BinOp, UnaryOp};
 use roc_parse::{
     ast::{
-        AbilityImpls, AbilityMember, AssignedField, Collection, CommentOrNewline, Defs, Expr,
+        AbilityImpls, AbilityMember, AssignedField, Base, Collection, CommentOrNewline, Defs, Expr,
         Header, Implements, ImplementsAbilities, ImplementsAbility, ImplementsClause, Module,
         Pattern, PatternAs, RecordBuilderField, Spaced, Spaces, StrLiteral, StrSegment, Tag,
         TypeAnnotation, TypeDef, TypeHeader, ValueDef, WhenBranch,
@@ -19,6 +19,14 @@ use roc_region::all::{Loc, Position, Region};
 
 use crate::{Ast, Buf};
 
+/// The formatter changes the case of hex digits, so that shouldn't count as a change to the AST.
+fn normalize_non_base10_digits<'a>(arena: &'a Bump, base: Base, string: &'a str) -> &'a str {
+    match base {
+        Base::Hex => arena.alloc_str(&string.to_ascii_uppercase()),
+        Base::Octal | Base::Binary | Base::Decimal => string,
+    }
+}
+
 /// The number of spaces to indent.
 pub const INDENT: u16 = 4;
 
@@ -670,7 +678,7 @@ impl<'a> RemoveSpaces<'a> for Expr<'a> {
                 base,
                 is_negative,
             } => Expr::NonBase10Int {
-                string,
+                string: normalize_non_base10_digits(arena, base, string),
                 base,
                 is_negative,
             },
@@ -811,7 +819,7 @@ impl<'a> RemoveSpaces<'a> for Pattern<'a> {
                 base,
                 is_negative,
             } => Pattern::NonBase10Literal {
-                string,
+                string: normalize_non_base10_digits(arena, base, string),
                 base,
                 is_negative,
             },
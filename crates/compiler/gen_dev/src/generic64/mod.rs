@@ -5,6 +5,7 @@ use crate::{
 use bumpalo::collections::{CollectIn, Vec};
 use roc_builtins::bitcode::{self, FloatWidth, IntWidth};
 use roc_collections::all::MutMap;
+use roc_debug_flags::{dbg_do, ROC_PRINT_DEV_REGISTER_PRESSURE};
 use roc_error_macros::{internal_error, todo_lambda_erasure};
 use roc_module::symbol::{Interns, ModuleId, Symbol};
 use roc_mono::code_gen_help::{CallerProc, CodeGenHelp, HelperOp};
@@ -897,6 +898,15 @@ impl<
     }
 
     fn finalize(&mut self) -> (Vec<u8>, Vec<Relocation>) {
+        dbg_do!(ROC_PRINT_DEV_REGISTER_PRESSURE, {
+            eprintln!(
+                "{}: peak register pressure of {} general and {} float registers",
+                self.proc_name.as_deref().unwrap_or_default(),
+                self.storage_manager.peak_general_pressure(),
+                self.storage_manager.peak_float_pressure(),
+            );
+        });
+
         let mut out = bumpalo::vec![in self.env.arena];
 
         // Setup stack.
@@ -1094,7 +1104,11 @@ impl<
             }
             single_register_floats!() => {
                 let dst_reg = self.storage_manager.claim_float_reg(&mut self.buf, dst);
-                ASM::mov_freg64_freg64(&mut self.buf, dst_reg, CC::FLOAT_RETURN_REGS[0]);
+                self.storage_manager.move_reg_if_needed(
+                    &mut self.buf,
+                    RegStorage::Float(dst_reg),
+                    RegStorage::Float(CC::FLOAT_RETURN_REGS[0]),
+                );
             }
             // Note that on windows there is only 1 general return register so we can't use this optimisation
             LayoutRepr::I128 | LayoutRepr::U128 if CC::GENERAL_RETURN_REGS.len() > 1 => {
@@ -1109,7 +1123,11 @@ impl<
             }
             pointer_layouts!() => {
                 let dst_reg = self.storage_manager.claim_general_reg(&mut self.buf, dst);
-                ASM::mov_reg64_reg64(&mut self.buf, dst_reg, CC::GENERAL_RETURN_REGS[0]);
+                self.storage_manager.move_reg_if_needed(
+                    &mut self.buf,
+                    RegStorage::General(dst_reg),
+                    RegStorage::General(CC::GENERAL_RETURN_REGS[0]),
+                );
             }
             LayoutRepr::LambdaSet(lambda_set) => {
                 self.move_return_value(dst, &lambda_set.runtime_representation())
@@ -1152,8 +1170,10 @@ impl<
             .load_to_general_reg(&mut self.buf, cond_symbol);
 
         // this state is updated destructively in the branches. We don't want the branches to
-        // influence each other, so we must clone here.
-        let mut base_storage = self.storage_manager.clone();
+        // influence each other, so we must snapshot it here.
+        // Used callee-saved registers and the function call stack size are not part of the
+        // snapshot, so they get saved/restored even if used in only one of the branches.
+        let base_storage = self.storage_manager.snapshot();
         let base_literal_map = self.literal_map.clone();
 
         let mut max_branch_stack_size = 0;
@@ -1174,7 +1194,7 @@ impl<
             );
 
             // Build all statements in this branch. Using storage as from before any branch.
-            self.storage_manager = base_storage.clone();
+            self.storage_manager.restore(&base_storage);
             self.literal_map = base_literal_map.clone();
             self.build_stmt(layout_ids, stmt, ret_layout);
 
@@ -1201,15 +1221,8 @@ impl<
             // Update important storage information to avoid overwrites.
             max_branch_stack_size =
                 std::cmp::max(max_branch_stack_size, self.storage_manager.stack_size());
-            base_storage.update_fn_call_stack_size(self.storage_manager.fn_call_stack_size());
-
-            // make sure that used callee-saved registers get saved/restored even if used in only
-            // one of the branches of the switch
-            base_storage
-                .used_callee_saved_regs
-                .extend(&self.storage_manager.used_callee_saved_regs);
         }
-        self.storage_manager = base_storage;
+        self.storage_manager.restore(&base_storage);
         self.literal_map = base_literal_map;
         self.storage_manager
             .update_stack_size(max_branch_stack_size);
@@ -1318,6 +1331,11 @@ impl<
                     .load_to_general_reg(&mut self.buf, src2);
                 ASM::add_reg64_reg64_reg64(&mut self.buf, dst_reg, src1_reg, src2_reg);
             }
+            LayoutRepr::Builtin(Builtin::Int(width @ (IntWidth::I128 | IntWidth::U128))) => {
+                // 128-bit integers live in a 16-byte stack slot, let zig do the carrying for us.
+                let intrinsic = bitcode::NUM_ADD_OR_PANIC_INT[width].to_string();
+                self.build_fn_call(dst, intrinsic, &[*src1, *src2], &[*layout, *layout], layout);
+            }
             LayoutRepr::Builtin(Builtin::Float(FloatWidth::F64)) => {
                 let dst_reg = self.storage_manager.claim_float_reg(&mut self.buf, dst);
                 let src1_reg = self.storage_manager.load_to_float_reg(&mut self.buf, src1);
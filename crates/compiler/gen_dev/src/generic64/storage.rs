@@ -57,7 +57,7 @@ enum StackStorage<GeneralReg: RegTrait, FloatReg: RegTrait> {
         // If not, it must be zero extended.
         sign_extend: bool,
     },
-    /// Complex data (lists, unions, structs, str) stored on the stack.
+    /// Complex data (lists, unions, structs, str, 128-bit numbers) stored on the stack.
     /// Note, this is also used for referencing a value within a struct/union.
     /// It has no alignment guarantees.
     /// When a primitive value is being loaded from this, it should be moved into a register.
@@ -107,14 +107,14 @@ pub struct StorageManager<
     // When jumping to the join point, the parameters should be setup to match this.
     join_param_map: MutMap<JoinPointId, Vec<'a, Storage<GeneralReg, FloatReg>>>,
 
-    // This should probably be smarter than a vec.
-    // There are certain registers we should always use first. With pushing and popping, this could get mixed.
+    // Caller saved registers are always handed out before callee saved ones,
+    // even if pushing and popping mixed up the order of the free lists.
     general_free_regs: Vec<'a, GeneralReg>,
     float_free_regs: Vec<'a, FloatReg>,
 
     // The last major thing we need is a way to decide what reg to free when all of them are full.
-    // Theoretically we want a basic lru cache for the currently loaded symbols.
-    // For now just a vec of used registers and the symbols they contain.
+    // The used registers and the symbols they contain are kept in least recently used order.
+    // The front of the vec is the register that will be freed first.
     general_used_regs: Vec<'a, (GeneralReg, Symbol)>,
     float_used_regs: Vec<'a, (FloatReg, Symbol)>,
 
@@ -126,6 +126,11 @@ pub struct StorageManager<
     /// Amount of extra stack space needed to pass arguments for a function call
     /// This is usually zero, and only used when the argument passing registers are all used
     fn_call_stack_size: u32,
+
+    /// The most registers that were in use at once, including temporary registers.
+    /// This is only kept for diagnostics, to see how close a function came to spilling.
+    peak_general_pressure: usize,
+    peak_float_pressure: usize,
 }
 
 pub fn new_storage_manager<
@@ -156,6 +161,8 @@ pub fn new_storage_manager<
         free_stack_chunks: bumpalo::vec![in env.arena],
         stack_size: 0,
         fn_call_stack_size: 0,
+        peak_general_pressure: 0,
+        peak_float_pressure: 0,
     }
 }
 
@@ -180,11 +187,6 @@ impl<GeneralReg: RegTrait, FloatReg: RegTrait> UsedCalleeRegisters<GeneralReg, F
         self.float.insert(reg)
     }
 
-    pub(crate) fn extend(&mut self, other: &Self) {
-        self.general.extend(other.general.iter().copied());
-        self.float.extend(other.float.iter().copied());
-    }
-
     pub(crate) fn as_vecs<'a>(
         &self,
         arena: &'a bumpalo::Bump,
@@ -205,6 +207,22 @@ impl<GeneralReg, FloatReg> Default for UsedCalleeRegisters<GeneralReg, FloatReg>
     }
 }
 
+/// A copy of where every symbol is stored, taken with `StorageManager::snapshot`.
+/// Restoring it rolls back any storage changes made by speculatively generated code.
+/// Callee saved registers used in the meantime are not rolled back, they still need to be saved.
+/// The same goes for the stack space needed to pass function call arguments.
+pub struct StorageSnapshot<'a, GeneralReg: RegTrait, FloatReg: RegTrait> {
+    symbol_storage_map: MutMap<Symbol, Storage<GeneralReg, FloatReg>>,
+    allocation_map: MutMap<Symbol, Rc<(i32, u32)>>,
+    join_param_map: MutMap<JoinPointId, Vec<'a, Storage<GeneralReg, FloatReg>>>,
+    general_free_regs: Vec<'a, GeneralReg>,
+    float_free_regs: Vec<'a, FloatReg>,
+    general_used_regs: Vec<'a, (GeneralReg, Symbol)>,
+    float_used_regs: Vec<'a, (FloatReg, Symbol)>,
+    free_stack_chunks: Vec<'a, (i32, u32)>,
+    stack_size: u32,
+}
+
 impl<
         'a,
         'r,
@@ -231,6 +249,37 @@ impl<
         self.free_stack_chunks.clear();
         self.stack_size = 0;
         self.fn_call_stack_size = 0;
+        self.peak_general_pressure = 0;
+        self.peak_float_pressure = 0;
+    }
+
+    /// Takes a snapshot of the current storage state so that it can later be restored.
+    pub fn snapshot(&self) -> StorageSnapshot<'a, GeneralReg, FloatReg> {
+        StorageSnapshot {
+            symbol_storage_map: self.symbol_storage_map.clone(),
+            allocation_map: self.allocation_map.clone(),
+            join_param_map: self.join_param_map.clone(),
+            general_free_regs: self.general_free_regs.clone(),
+            float_free_regs: self.float_free_regs.clone(),
+            general_used_regs: self.general_used_regs.clone(),
+            float_used_regs: self.float_used_regs.clone(),
+            free_stack_chunks: self.free_stack_chunks.clone(),
+            stack_size: self.stack_size,
+        }
+    }
+
+    /// Restores the storage state to what it was when the snapshot was taken.
+    /// The snapshot is left untouched, so it can be restored multiple times.
+    pub fn restore(&mut self, snapshot: &StorageSnapshot<'a, GeneralReg, FloatReg>) {
+        self.symbol_storage_map = snapshot.symbol_storage_map.clone();
+        self.allocation_map = snapshot.allocation_map.clone();
+        self.join_param_map = snapshot.join_param_map.clone();
+        self.general_free_regs = snapshot.general_free_regs.clone();
+        self.float_free_regs = snapshot.float_free_regs.clone();
+        self.general_used_regs = snapshot.general_used_regs.clone();
+        self.float_used_regs = snapshot.float_used_regs.clone();
+        self.free_stack_chunks = snapshot.free_stack_chunks.clone();
+        self.stack_size = snapshot.stack_size;
     }
 
     pub fn stack_size(&self) -> u32 {
@@ -241,6 +290,32 @@ impl<
         self.fn_call_stack_size
     }
 
+    /// The most general registers that were in use at once since the last reset.
+    pub fn peak_general_pressure(&self) -> usize {
+        self.peak_general_pressure
+    }
+
+    /// The most float registers that were in use at once since the last reset.
+    pub fn peak_float_pressure(&self) -> usize {
+        self.peak_float_pressure
+    }
+
+    /// Updates the peak general register pressure after a register is taken from the free list.
+    fn update_general_pressure(&mut self) {
+        let in_use = CC::GENERAL_DEFAULT_FREE_REGS
+            .len()
+            .saturating_sub(self.general_free_regs.len());
+        self.peak_general_pressure = max(self.peak_general_pressure, in_use);
+    }
+
+    /// Updates the peak float register pressure after a register is taken from the free list.
+    fn update_float_pressure(&mut self) {
+        let in_use = CC::FLOAT_DEFAULT_FREE_REGS
+            .len()
+            .saturating_sub(self.float_free_regs.len());
+        self.peak_float_pressure = max(self.peak_float_pressure, in_use);
+    }
+
     /// Returns true if the symbol is storing a primitive value.
     pub fn is_stored_primitive(&self, sym: &Symbol) -> bool {
         matches!(
@@ -249,37 +324,102 @@ impl<
         )
     }
 
+    /// Returns the register currently holding the symbol, if any.
+    /// Unlike the load functions, this never moves the symbol or emits any code.
+    pub fn reg_for_sym(&self, sym: &Symbol) -> Option<RegStorage<GeneralReg, FloatReg>> {
+        match self.get_storage_for_sym(sym) {
+            Reg(reg_storage)
+            | Stack(Primitive {
+                reg: Some(reg_storage),
+                ..
+            }) => Some(*reg_storage),
+            _ => None,
+        }
+    }
+
+    /// Removes a register from the free list, preferring the most recently freed caller saved register.
+    /// Callee saved registers must be pushed and popped in the prologue and epilogue,
+    /// so they are only handed out once no caller saved register is left.
+    fn pop_free_reg<Reg: RegTrait>(
+        free_regs: &mut Vec<'a, Reg>,
+        is_caller_saved: fn(&Reg) -> bool,
+    ) -> Option<Reg> {
+        match free_regs.iter().rposition(is_caller_saved) {
+            Some(position) => Some(free_regs.remove(position)),
+            None => free_regs.pop(),
+        }
+    }
+
     /// Get a general register from the free list.
     /// Will free data to the stack if necessary to get the register.
-    fn get_general_reg(&mut self, buf: &mut Vec<'a, u8>) -> GeneralReg {
-        if let Some(reg) = self.general_free_regs.pop() {
+    /// The symbol the register is for, if any, is only used to report running out of registers.
+    fn get_general_reg(&mut self, buf: &mut Vec<'a, u8>, for_sym: Option<&Symbol>) -> GeneralReg {
+        if let Some(reg) = Self::pop_free_reg(&mut self.general_free_regs, CC::general_caller_saved)
+        {
             if CC::general_callee_saved(&reg) {
                 self.used_callee_saved_regs.insert_general(reg);
             }
+            self.update_general_pressure();
             reg
         } else if !self.general_used_regs.is_empty() {
+            // Evict the least recently used register.
             let (reg, sym) = self.general_used_regs.remove(0);
             self.free_to_stack(buf, &sym, General(reg));
             reg
         } else {
-            internal_error!("completely out of general purpose registers");
+            internal_error!(
+                "completely out of general purpose registers while claiming one for {:?}, too many values are live at once. Registers holding symbols: {:?}",
+                for_sym,
+                self.general_used_regs
+            );
         }
     }
 
     /// Get a float register from the free list.
     /// Will free data to the stack if necessary to get the register.
-    fn get_float_reg(&mut self, buf: &mut Vec<'a, u8>) -> FloatReg {
-        if let Some(reg) = self.float_free_regs.pop() {
+    /// The symbol the register is for, if any, is only used to report running out of registers.
+    fn get_float_reg(&mut self, buf: &mut Vec<'a, u8>, for_sym: Option<&Symbol>) -> FloatReg {
+        if let Some(reg) = Self::pop_free_reg(&mut self.float_free_regs, CC::float_caller_saved) {
             if CC::float_callee_saved(&reg) {
                 self.used_callee_saved_regs.insert_float(reg);
             }
+            self.update_float_pressure();
             reg
         } else if !self.float_used_regs.is_empty() {
+            // Evict the least recently used register.
             let (reg, sym) = self.float_used_regs.remove(0);
             self.free_to_stack(buf, &sym, Float(reg));
             reg
         } else {
-            internal_error!("completely out of float registers");
+            internal_error!(
+                "completely out of float registers while claiming one for {:?}, too many values are live at once. Registers holding symbols: {:?}",
+                for_sym,
+                self.float_used_regs
+            );
+        }
+    }
+
+    /// Marks a general reg as the most recently used one, pushing it to the back of the eviction order.
+    fn touch_general_reg(&mut self, reg: GeneralReg) {
+        if let Some(position) = self
+            .general_used_regs
+            .iter()
+            .position(|(used_reg, _sym)| *used_reg == reg)
+        {
+            let entry = self.general_used_regs.remove(position);
+            self.general_used_regs.push(entry);
+        }
+    }
+
+    /// Marks a float reg as the most recently used one, pushing it to the back of the eviction order.
+    fn touch_float_reg(&mut self, reg: FloatReg) {
+        if let Some(position) = self
+            .float_used_regs
+            .iter()
+            .position(|(used_reg, _sym)| *used_reg == reg)
+        {
+            let entry = self.float_used_regs.remove(position);
+            self.float_used_regs.push(entry);
         }
     }
 
@@ -291,7 +431,7 @@ impl<
             None,
             "Symbol {sym:?} is already in the storage map!"
         );
-        let reg = self.get_general_reg(buf);
+        let reg = self.get_general_reg(buf, Some(sym));
         self.general_used_regs.push((reg, *sym));
         self.symbol_storage_map.insert(*sym, Reg(General(reg)));
         reg
@@ -301,35 +441,39 @@ impl<
     /// They symbol should not already have storage.
     pub fn claim_float_reg(&mut self, buf: &mut Vec<'a, u8>, sym: &Symbol) -> FloatReg {
         debug_assert_eq!(self.symbol_storage_map.get(sym), None);
-        let reg = self.get_float_reg(buf);
+        let reg = self.get_float_reg(buf, Some(sym));
         self.float_used_regs.push((reg, *sym));
         self.symbol_storage_map.insert(*sym, Reg(Float(reg)));
         reg
     }
 
     /// This claims a temporary general register and enables is used in the passed in function.
+    /// Whatever the passed in function returns is returned once the register has been released.
     /// Temporary registers are not safe across call instructions.
-    pub fn with_tmp_general_reg<F: FnOnce(&mut Self, &mut Vec<'a, u8>, GeneralReg)>(
+    pub fn with_tmp_general_reg<T, F: FnOnce(&mut Self, &mut Vec<'a, u8>, GeneralReg) -> T>(
         &mut self,
         buf: &mut Vec<'a, u8>,
         callback: F,
-    ) {
-        let reg = self.get_general_reg(buf);
-        callback(self, buf, reg);
+    ) -> T {
+        let reg = self.get_general_reg(buf, None);
+        let result = callback(self, buf, reg);
         self.general_free_regs.push(reg);
+        result
     }
 
     #[allow(dead_code)]
     /// This claims a temporary float register and enables is used in the passed in function.
+    /// Whatever the passed in function returns is returned once the register has been released.
     /// Temporary registers are not safe across call instructions.
-    pub fn with_tmp_float_reg<F: FnOnce(&mut Self, &mut Vec<'a, u8>, FloatReg)>(
+    pub fn with_tmp_float_reg<T, F: FnOnce(&mut Self, &mut Vec<'a, u8>, FloatReg) -> T>(
         &mut self,
         buf: &mut Vec<'a, u8>,
         callback: F,
-    ) {
-        let reg = self.get_float_reg(buf);
-        callback(self, buf, reg);
+    ) -> T {
+        let reg = self.get_float_reg(buf, None);
+        let result = callback(self, buf, reg);
         self.float_free_regs.push(reg);
+        result
     }
 
     /// Loads a symbol into a general reg and returns that register.
@@ -345,6 +489,7 @@ impl<
                 ..
             }) => {
                 self.symbol_storage_map.insert(*sym, storage);
+                self.touch_general_reg(reg);
                 reg
             }
             Reg(Float(_))
@@ -359,7 +504,7 @@ impl<
                 base_offset,
             }) => {
                 debug_assert_eq!(base_offset % 8, 0);
-                let reg = self.get_general_reg(buf);
+                let reg = self.get_general_reg(buf, Some(sym));
                 ASM::mov_reg64_base32(buf, reg, base_offset);
                 self.general_used_regs.push((reg, *sym));
                 self.symbol_storage_map.insert(
@@ -376,21 +521,14 @@ impl<
                 size,
                 sign_extend,
             }) => {
-                let reg = self.get_general_reg(buf);
-
-                let register_width = match size {
-                    8 => RegisterWidth::W64,
-                    4 => RegisterWidth::W32,
-                    2 => RegisterWidth::W16,
-                    1 => RegisterWidth::W8,
-                    _ => internal_error!("Invalid size: {size}"),
-                };
-
-                if sign_extend {
-                    ASM::movsx_reg_base32(buf, register_width, reg, base_offset);
-                } else {
-                    ASM::movzx_reg_base32(buf, register_width, reg, base_offset);
-                }
+                let reg = self.get_general_reg(buf, Some(sym));
+                Self::load_referenced_primitive_to_general_reg(
+                    buf,
+                    reg,
+                    base_offset,
+                    size,
+                    sign_extend,
+                );
                 self.general_used_regs.push((reg, *sym));
                 self.symbol_storage_map.insert(*sym, Reg(General(reg)));
                 self.free_reference(sym);
@@ -420,6 +558,7 @@ impl<
                 ..
             }) => {
                 self.symbol_storage_map.insert(*sym, storage);
+                self.touch_float_reg(reg);
                 reg
             }
             Reg(General(_))
@@ -434,7 +573,7 @@ impl<
                 base_offset,
             }) => {
                 debug_assert_eq!(base_offset % 8, 0);
-                let reg = self.get_float_reg(buf);
+                let reg = self.get_float_reg(buf, Some(sym));
                 ASM::mov_freg64_base32(buf, reg, base_offset);
                 self.float_used_regs.push((reg, *sym));
                 self.symbol_storage_map.insert(
@@ -448,18 +587,14 @@ impl<
             }
             Stack(ReferencedPrimitive {
                 base_offset, size, ..
-            }) if base_offset % 8 == 0 && size == 8 => {
-                // The primitive is aligned and the data is exactly 8 bytes, treat it like regular stack.
-                let reg = self.get_float_reg(buf);
-                ASM::mov_freg64_base32(buf, reg, base_offset);
+            }) => {
+                let reg = self.get_float_reg(buf, Some(sym));
+                Self::load_referenced_primitive_to_float_reg(buf, reg, base_offset, size);
                 self.float_used_regs.push((reg, *sym));
                 self.symbol_storage_map.insert(*sym, Reg(Float(reg)));
                 self.free_reference(sym);
                 reg
             }
-            Stack(ReferencedPrimitive { .. }) => {
-                todo!("loading referenced primitives")
-            }
             Stack(Complex { .. }) => {
                 internal_error!("Cannot load large values into float registers: {}", sym)
             }
@@ -486,10 +621,7 @@ impl<
                 reg: Some(General(old_reg)),
                 ..
             }) => {
-                if *old_reg == reg {
-                    return;
-                }
-                ASM::mov_reg64_reg64(buf, reg, *old_reg);
+                self.move_reg_if_needed(buf, General(reg), General(*old_reg));
             }
             Reg(Float(_))
             | Stack(Primitive {
@@ -510,21 +642,13 @@ impl<
                 size,
                 sign_extend,
             }) => {
-                debug_assert!(*size <= 8);
-
-                let register_width = match size {
-                    8 => RegisterWidth::W64,
-                    4 => RegisterWidth::W32,
-                    2 => RegisterWidth::W16,
-                    1 => RegisterWidth::W8,
-                    _ => internal_error!("Invalid size: {size}"),
-                };
-
-                if *sign_extend {
-                    ASM::movsx_reg_base32(buf, register_width, reg, *base_offset)
-                } else {
-                    ASM::movzx_reg_base32(buf, register_width, reg, *base_offset)
-                }
+                Self::load_referenced_primitive_to_general_reg(
+                    buf,
+                    reg,
+                    *base_offset,
+                    *size,
+                    *sign_extend,
+                );
             }
             Stack(Complex { size, .. }) => {
                 internal_error!(
@@ -549,10 +673,7 @@ impl<
                 reg: Some(Float(old_reg)),
                 ..
             }) => {
-                if *old_reg == reg {
-                    return;
-                }
-                ASM::mov_freg64_freg64(buf, reg, *old_reg);
+                self.move_reg_if_needed(buf, Float(reg), Float(*old_reg));
             }
             Reg(General(_))
             | Stack(Primitive {
@@ -570,12 +691,8 @@ impl<
             }
             Stack(ReferencedPrimitive {
                 base_offset, size, ..
-            }) if base_offset % 8 == 0 && *size == 8 => {
-                // The primitive is aligned and the data is exactly 8 bytes, treat it like regular stack.
-                ASM::mov_freg64_base32(buf, reg, *base_offset);
-            }
-            Stack(ReferencedPrimitive { .. }) => {
-                todo!("loading referenced primitives")
+            }) => {
+                Self::load_referenced_primitive_to_float_reg(buf, reg, *base_offset, *size);
             }
             Stack(Complex { .. }) => {
                 internal_error!("Cannot load large values into float registers: {}", sym)
@@ -586,6 +703,203 @@ impl<
         }
     }
 
+    /// Loads every argument symbol into its general argument register for a call.
+    /// The moves between registers are done as one parallel move, so arguments that are in each
+    /// others registers (for example, two symbols that need to swap) are never clobbered.
+    /// Cycles are broken with a scratch register.
+    /// A symbol whose register gets overwritten now lives in its argument register,
+    /// any other symbol keeps its register and is copied.
+    /// Arguments that are not in registers are loaded once the registers are setup.
+    pub fn setup_call_args(&mut self, buf: &mut Vec<'a, u8>, args: &[(Symbol, GeneralReg)]) {
+        let is_arg = |sym: &Symbol| args.iter().any(|(arg, _)| arg == sym);
+        let is_target = |reg: &GeneralReg| args.iter().any(|(_, target)| target == reg);
+
+        // Any other symbol in an argument register is in the way.
+        for (_, target) in args {
+            let in_the_way = self
+                .general_used_regs
+                .iter()
+                .any(|(used_reg, sym)| used_reg == target && !is_arg(sym));
+            if in_the_way {
+                self.ensure_reg_free(buf, General(*target));
+            }
+        }
+
+        // Only the first use of a symbol is moved, any other uses copy it afterwards.
+        let mut moves: Vec<'a, (Symbol, GeneralReg, GeneralReg)> = bumpalo::vec![in self.env.arena];
+        for (i, (sym, target)) in args.iter().enumerate() {
+            if args[..i].iter().any(|(arg, _)| arg == sym) {
+                continue;
+            }
+            if let Some(General(reg)) = self.reg_for_sym(sym) {
+                moves.push((*sym, reg, *target));
+            }
+        }
+
+        let mut pending: Vec<'a, (GeneralReg, GeneralReg)> = moves
+            .iter()
+            .filter(|(_, src, dst)| src != dst)
+            .map(|(_, src, dst)| (*src, *dst))
+            .collect_in(self.env.arena);
+        while !pending.is_empty() {
+            // A move is safe once nothing else still needs to read its destination.
+            match pending
+                .iter()
+                .position(|(_, dst)| !pending.iter().any(|(src, _)| src == dst))
+            {
+                Some(position) => {
+                    let (src, dst) = pending.remove(position);
+                    self.move_reg_if_needed(buf, General(dst), General(src));
+                }
+                None => {
+                    // Everything left is part of a cycle.
+                    // Moving one source out of the way lets the rest of its cycle go through.
+                    let scratch = self.get_scratch_general_reg(buf, |reg, sym| {
+                        is_target(reg) || sym.map_or(false, is_arg)
+                    });
+                    let (src, _) = pending[0];
+                    self.move_reg_if_needed(buf, General(scratch), General(src));
+                    pending[0].0 = scratch;
+                }
+            }
+        }
+
+        for (sym, src, dst) in moves {
+            if src == dst || !is_target(&src) {
+                // The symbol is still in its register.
+                continue;
+            }
+            if let Some(entry) = self
+                .general_used_regs
+                .iter_mut()
+                .find(|(used_reg, used_sym)| *used_reg == src && *used_sym == sym)
+            {
+                entry.0 = dst;
+            }
+            self.general_free_regs.retain(|reg| *reg != dst);
+            if !self.general_used_regs.iter().any(|(reg, _)| *reg == src) {
+                self.general_free_regs.push(src);
+            }
+            let storage = match self.remove_storage_for_sym(&sym) {
+                Reg(_) => Reg(General(dst)),
+                Stack(Primitive { base_offset, .. }) => Stack(Primitive {
+                    base_offset,
+                    reg: Some(General(dst)),
+                }),
+                storage => internal_error!("{sym:?} was moved out of {storage:?}"),
+            };
+            self.symbol_storage_map.insert(sym, storage);
+        }
+
+        for (sym, target) in args {
+            self.load_to_specified_general_reg(buf, sym, *target);
+        }
+    }
+
+    /// Gets a general register that can be clobbered without claiming it.
+    /// If no free register is allowed, the symbol in an allowed used register is freed to the stack.
+    fn get_scratch_general_reg(
+        &mut self,
+        buf: &mut Vec<'a, u8>,
+        is_excluded: impl Fn(&GeneralReg, Option<&Symbol>) -> bool,
+    ) -> GeneralReg {
+        let free_reg = self
+            .general_free_regs
+            .iter()
+            .filter(|reg| !is_excluded(reg, None))
+            .rev()
+            .find(|reg| CC::general_caller_saved(reg))
+            .or_else(|| {
+                self.general_free_regs
+                    .iter()
+                    .rev()
+                    .find(|reg| !is_excluded(reg, None))
+            })
+            .copied();
+        if let Some(reg) = free_reg {
+            if CC::general_callee_saved(&reg) {
+                self.used_callee_saved_regs.insert_general(reg);
+            }
+            return reg;
+        }
+
+        let used_reg = self
+            .general_used_regs
+            .iter()
+            .find(|(reg, sym)| !is_excluded(reg, Some(sym)))
+            .map(|(reg, _)| *reg);
+        match used_reg {
+            Some(reg) => {
+                self.ensure_reg_free(buf, General(reg));
+                reg
+            }
+            None => internal_error!(
+                "completely out of general purpose registers while looking for a scratch register, used registers: {:?}",
+                self.general_used_regs
+            ),
+        }
+    }
+
+    /// Moves the value in `src` to `dst`.
+    /// Nothing is emitted if the value is already in the right register.
+    /// Every move of a symbol into a specific register should go through here to avoid self-moves.
+    pub fn move_reg_if_needed(
+        &self,
+        buf: &mut Vec<'a, u8>,
+        dst: RegStorage<GeneralReg, FloatReg>,
+        src: RegStorage<GeneralReg, FloatReg>,
+    ) {
+        match (dst, src) {
+            _ if dst == src => {}
+            (General(dst), General(src)) => ASM::mov_reg64_reg64(buf, dst, src),
+            (Float(dst), Float(src)) => ASM::mov_freg64_freg64(buf, dst, src),
+            _ => internal_error!(
+                "Cannot move {src:?} into {dst:?}, they are different kinds of registers"
+            ),
+        }
+    }
+
+    /// Loads a referenced primitive into `reg`.
+    /// Only `size` bytes are read, so the data around the primitive is never touched.
+    /// The rest of the register is filled with the sign or zero extension of the value.
+    fn load_referenced_primitive_to_general_reg(
+        buf: &mut Vec<'a, u8>,
+        reg: GeneralReg,
+        base_offset: i32,
+        size: u32,
+        sign_extend: bool,
+    ) {
+        let register_width = match size {
+            8 => RegisterWidth::W64,
+            4 => RegisterWidth::W32,
+            2 => RegisterWidth::W16,
+            1 => RegisterWidth::W8,
+            _ => internal_error!("Invalid size: {size}"),
+        };
+
+        if sign_extend {
+            ASM::movsx_reg_base32(buf, register_width, reg, base_offset);
+        } else {
+            ASM::movzx_reg_base32(buf, register_width, reg, base_offset);
+        }
+    }
+
+    /// Loads a referenced float into `reg`.
+    /// The data is not guaranteed to be 8 byte aligned, so it is read through the base pointer
+    /// with a load of exactly `size` bytes.
+    fn load_referenced_primitive_to_float_reg(
+        buf: &mut Vec<'a, u8>,
+        reg: FloatReg,
+        base_offset: i32,
+        size: u32,
+    ) {
+        match size {
+            8 => ASM::mov_freg64_mem64_offset32(buf, reg, CC::BASE_PTR_REG, base_offset),
+            4 => ASM::mov_freg32_mem32_offset32(buf, reg, CC::BASE_PTR_REG, base_offset),
+            _ => internal_error!("Invalid float size: {size}"),
+        }
+    }
+
     /// Loads a field from a struct or tag union.
     /// This is lazy by default. It will not copy anything around.
     pub fn load_field_at_index(
@@ -935,7 +1249,6 @@ impl<
         });
     }
 
-    #[allow(dead_code)]
     /// Ensures that a register is free. If it is not free, data will be moved to make it free.
     pub fn ensure_reg_free(
         &mut self,
@@ -1107,6 +1420,7 @@ impl<
         self.symbol_storage_map.insert(*sym, Reg(General(reg)));
         self.general_free_regs.retain(|r| *r != reg);
         self.general_used_regs.push((reg, *sym));
+        self.update_general_pressure();
     }
 
     /// Specifies a symbol is loaded at the specified float register.
@@ -1114,6 +1428,7 @@ impl<
         self.symbol_storage_map.insert(*sym, Reg(Float(reg)));
         self.float_free_regs.retain(|r| *r != reg);
         self.float_used_regs.push((reg, *sym));
+        self.update_float_pressure();
     }
 
     /// Specifies a primitive is loaded at the specific base offset.
@@ -1147,6 +1462,7 @@ impl<
             .insert(Symbol::RET_POINTER, Reg(General(reg)));
         self.general_free_regs.retain(|x| *x != reg);
         self.general_used_regs.push((reg, Symbol::RET_POINTER));
+        self.update_general_pressure();
     }
 
     /// updates the stack size to the max of its current value and the tmp size needed.
@@ -1279,19 +1595,23 @@ impl<
                 Reg(_) => {
                     internal_error!("Register storage is not allowed for jumping to joinpoint")
                 }
-                Stack(Complex { base_offset, .. }) => {
-                    // TODO: This might be better not to call.
-                    // Maybe we want a more memcpy like method to directly get called here.
-                    // That would also be capable of asserting the size.
-                    // Maybe copy stack to stack or something.
-                    self.copy_symbol_to_stack_offset(
-                        layout_interner,
-                        buf,
-                        *base_offset,
-                        sym,
-                        layout,
-                    );
-                }
+                Stack(Complex { base_offset, size }) => match self.get_storage_for_sym(sym) {
+                    Stack(Complex { .. }) => {
+                        // Both values live on the stack, so this is a plain memcpy.
+                        let (from_offset, from_size) = self.stack_offset_and_size(sym);
+                        debug_assert_eq!(from_size, *size);
+                        self.copy_to_stack_offset(buf, *size, from_offset, *base_offset);
+                    }
+                    _ => {
+                        self.copy_symbol_to_stack_offset(
+                            layout_interner,
+                            buf,
+                            *base_offset,
+                            sym,
+                            layout,
+                        );
+                    }
+                },
                 Stack(Primitive {
                     base_offset,
                     reg: None,
@@ -1395,11 +1715,20 @@ impl<
             }
         }
 
+        // the first offset at or after `offset` that satisfies the alignment.
+        let aligned_start = |offset: i32| -> i32 {
+            match offset.rem_euclid(alignment as i32) {
+                0 => offset,
+                r => offset + (alignment as i32 - r),
+            }
+        };
+
         // round value to the alignment.
         let amount = next_multiple_of(amount, alignment);
 
         let chunk_fits = |(offset, size): &(i32, u32)| {
-            *size >= amount && is_multiple_of(*offset, alignment as i32)
+            let chunk_padding = (aligned_start(*offset) - *offset) as u32;
+            *size >= chunk_padding + amount
         };
 
         // padding on the stack to make sure an allocation is aligned
@@ -1413,21 +1742,36 @@ impl<
         {
             let (pos, (offset, size)) = fitting_chunk;
             let (offset, size) = (*offset, *size);
-            if size == amount {
-                // fill the whole chunk precisely
-                free_stack_chunks.remove(pos);
-                offset
-            } else {
-                // use part of this chunk
-                let (prev_offset, prev_size) = free_stack_chunks[pos];
-                free_stack_chunks[pos] = (prev_offset + amount as i32, prev_size - amount);
-                prev_offset
-            }
+            let start = aligned_start(offset);
+            let chunk_padding = (start - offset) as u32;
+            let remainder = size - chunk_padding - amount;
+
+            // The chunk is split into the padding before the allocation, the allocation itself,
+            // and whatever remains after it. Only the allocation is taken out of the free list.
+            debug_assert!(is_multiple_of(start, alignment as i32));
+            free_stack_chunks.remove(pos);
+            let mut insert_pos = pos;
+            if chunk_padding > 0 {
+                free_stack_chunks.insert(insert_pos, (offset, chunk_padding));
+                insert_pos += 1;
+            }
+            if remainder > 0 {
+                free_stack_chunks.insert(insert_pos, (start + amount as i32, remainder));
+            }
+            start
         } else if let Some(new_size) = stack_size.checked_add(padding + amount) {
             // Since stack size is u32, but the max offset is i32, if we pass i32 max, we have overflowed.
             if new_size > i32::MAX as u32 {
                 internal_error!("Ran out of stack space");
             } else {
+                if padding > 0 {
+                    // Keep the padding around, later allocations with a smaller alignment can use it.
+                    let padding_chunk = (-((*stack_size + padding) as i32), padding);
+                    let pos = free_stack_chunks
+                        .binary_search(&padding_chunk)
+                        .unwrap_or_else(|e| e);
+                    free_stack_chunks.insert(pos, padding_chunk);
+                }
                 *stack_size = new_size;
                 -(*stack_size as i32)
             }
@@ -1482,6 +1826,43 @@ impl<
                 break;
             }
         }
+
+        #[cfg(debug_assertions)]
+        self.check_free_stack_chunks();
+    }
+
+    /// Checks that no free stack chunk overlaps the stack storage of a symbol that is still alive.
+    /// If this fails, a stack chunk was freed while something still referenced it.
+    #[cfg(debug_assertions)]
+    fn check_free_stack_chunks(&self) {
+        for (sym, storage) in self.symbol_storage_map.iter() {
+            let (base_offset, size) = match storage {
+                Stack(Primitive { base_offset, .. }) => (*base_offset, 8),
+                Stack(
+                    ReferencedPrimitive {
+                        base_offset, size, ..
+                    }
+                    | Complex { base_offset, size },
+                ) => (*base_offset, *size),
+                Reg(_) | NoData => continue,
+            };
+            if size == 0 {
+                continue;
+            }
+
+            let end = base_offset + size as i32;
+            for (chunk_offset, chunk_size) in self.free_stack_chunks.iter() {
+                let chunk_end = *chunk_offset + *chunk_size as i32;
+                if base_offset < chunk_end && *chunk_offset < end {
+                    internal_error!(
+                        "The free stack chunk {:?} overlaps the storage of live symbol {:?}: {:?}",
+                        (chunk_offset, chunk_size),
+                        sym,
+                        storage
+                    );
+                }
+            }
+        }
     }
 
     /// Frees an reference and release an allocation if it is no longer used.
@@ -1624,11 +2005,30 @@ mod tests {
         X86_64Assembler, X86_64FloatReg, X86_64GeneralReg, X86_64SystemV,
     };
 
+    use crate::AssemblyBackendMode;
+    use roc_module::symbol::ModuleId;
+
     use super::*;
 
     type SystemVStorageManager<'a, 'r> =
         StorageManager<'a, 'r, X86_64GeneralReg, X86_64FloatReg, X86_64Assembler, X86_64SystemV>;
 
+    fn test_env(arena: &bumpalo::Bump) -> Env<'_> {
+        Env {
+            arena,
+            module_id: ModuleId::ATTR,
+            exposed_to_host: MutSet::default(),
+            lazy_literals: false,
+            mode: AssemblyBackendMode::Test,
+        }
+    }
+
+    fn test_storage_manager<'a, 'r>(env: &'r Env<'a>) -> SystemVStorageManager<'a, 'r> {
+        let mut storage_manager = new_storage_manager(env, TargetInfo::default_x86_64());
+        storage_manager.reset();
+        storage_manager
+    }
+
     fn claim_helper(
         mut free_stack_chunks: Vec<'_, (i32, u32)>,
         mut stack_size: u32,
@@ -1680,4 +2080,440 @@ mod tests {
             (16, -8, vec![in &arena; ])
         );
     }
+
+    #[test]
+    fn claim_stack_memory_aligned_16() {
+        use bumpalo::vec;
+        let arena = bumpalo::Bump::new();
+
+        // the padding needed to align a new allocation is kept as a free chunk
+        let (stack_size, offset, free_stack_chunks) = claim_helper(vec![in &arena;], 8, 16, 16);
+        assert_eq!(offset % 16, 0);
+        assert_eq!(
+            (stack_size, offset, free_stack_chunks),
+            (32, -32, vec![in &arena; (-16, 8)])
+        );
+
+        // a free chunk that does not start aligned can still hold an aligned allocation
+        let (stack_size, offset, free_stack_chunks) =
+            claim_helper(vec![in &arena; (-40, 32)], 48, 16, 16);
+        assert_eq!(offset % 16, 0);
+        assert_eq!(
+            (stack_size, offset, free_stack_chunks),
+            (48, -32, vec![in &arena; (-40, 8), (-16, 8)])
+        );
+
+        // the reclaimed padding is used by later allocations
+        assert_eq!(
+            claim_helper(vec![in &arena; (-16, 8)], 32, 8, 8),
+            (32, -16, vec![in &arena;])
+        );
+    }
+
+    #[test]
+    fn evict_least_recently_used_general_reg() {
+        let arena = bumpalo::Bump::new();
+        let env = test_env(&arena);
+        let mut storage_manager = test_storage_manager(&env);
+        let mut buf = bumpalo::vec![in &arena];
+
+        let syms = [
+            Symbol::DEV_TMP,
+            Symbol::DEV_TMP2,
+            Symbol::DEV_TMP3,
+            Symbol::DEV_TMP4,
+            Symbol::DEV_TMP5,
+        ];
+
+        let first_reg = storage_manager.claim_general_reg(&mut buf, &syms[0]);
+        for sym in &syms[1..] {
+            storage_manager.claim_general_reg(&mut buf, sym);
+        }
+
+        // Using the oldest symbol makes it the most recently used one.
+        assert_eq!(
+            storage_manager.load_to_general_reg(&mut buf, &syms[0]),
+            first_reg
+        );
+        assert_eq!(storage_manager.general_used_regs[0].1, syms[1]);
+        assert_eq!(
+            storage_manager.general_used_regs.last(),
+            Some(&(first_reg, syms[0]))
+        );
+
+        // With no free registers left, the least recently used symbol is spilled instead.
+        storage_manager.general_free_regs.clear();
+        let reg = storage_manager.get_general_reg(&mut buf, None);
+        assert_ne!(reg, first_reg);
+        assert!(matches!(
+            storage_manager.get_storage_for_sym(&syms[1]),
+            Stack(Primitive { reg: None, .. })
+        ));
+        assert!(matches!(
+            storage_manager.get_storage_for_sym(&syms[0]),
+            Reg(General(_))
+        ));
+    }
+
+    #[test]
+    fn evict_least_recently_used_float_reg() {
+        let arena = bumpalo::Bump::new();
+        let env = test_env(&arena);
+        let mut storage_manager = test_storage_manager(&env);
+        let mut buf = bumpalo::vec![in &arena];
+
+        let syms = [Symbol::DEV_TMP, Symbol::DEV_TMP2, Symbol::DEV_TMP3];
+        let regs: std::vec::Vec<_> = syms
+            .iter()
+            .map(|sym| storage_manager.claim_float_reg(&mut buf, sym))
+            .collect();
+
+        // Use the symbols out of claim order, so the second one is now the least recently used.
+        storage_manager.load_to_float_reg(&mut buf, &syms[0]);
+        storage_manager.load_to_float_reg(&mut buf, &syms[2]);
+
+        // Without the touches, the first claimed register would be evicted.
+        storage_manager.float_free_regs.clear();
+        let reg = storage_manager.get_float_reg(&mut buf, None);
+        assert_eq!(reg, regs[1]);
+        assert!(matches!(
+            storage_manager.get_storage_for_sym(&syms[1]),
+            Stack(Primitive { reg: None, .. })
+        ));
+        assert_eq!(
+            storage_manager.get_storage_for_sym(&syms[0]),
+            &Reg(Float(regs[0]))
+        );
+        assert_eq!(
+            storage_manager.get_storage_for_sym(&syms[2]),
+            &Reg(Float(regs[2]))
+        );
+    }
+
+    #[test]
+    fn prefer_caller_saved_general_regs() {
+        let arena = bumpalo::Bump::new();
+        let env = test_env(&arena);
+        let mut storage_manager = test_storage_manager(&env);
+        let mut buf = bumpalo::vec![in &arena];
+
+        // Freeing registers out of order can leave a callee saved register at the end of the free list.
+        storage_manager
+            .general_free_regs
+            .retain(|reg| *reg != X86_64GeneralReg::RBX);
+        storage_manager
+            .general_free_regs
+            .push(X86_64GeneralReg::RBX);
+
+        let syms = [Symbol::DEV_TMP, Symbol::DEV_TMP2, Symbol::DEV_TMP3];
+
+        for sym in &syms {
+            let reg = storage_manager.claim_general_reg(&mut buf, sym);
+            assert!(X86_64SystemV::general_caller_saved(&reg));
+        }
+        for sym in &syms {
+            storage_manager.free_symbol(sym);
+        }
+        for sym in &syms {
+            storage_manager.claim_general_reg(&mut buf, sym);
+        }
+
+        assert!(storage_manager.used_callee_saved_regs.general.is_empty());
+    }
+
+    #[test]
+    fn free_chunks_do_not_overlap_live_storage() {
+        let arena = bumpalo::Bump::new();
+        let env = test_env(&arena);
+        let mut storage_manager = test_storage_manager(&env);
+
+        storage_manager.claim_stack_area_with_alignment(Symbol::DEV_TMP, 16, 8);
+        storage_manager.claim_stack_area_with_alignment(Symbol::DEV_TMP2, 16, 8);
+        storage_manager.free_symbol(&Symbol::DEV_TMP);
+
+        assert_eq!(storage_manager.free_stack_chunks.as_slice(), &[(-16, 16)]);
+    }
+
+    #[test]
+    #[cfg(debug_assertions)]
+    #[should_panic(expected = "overlaps the storage of live symbol")]
+    fn free_chunk_overlapping_live_storage_panics() {
+        let arena = bumpalo::Bump::new();
+        let env = test_env(&arena);
+        let mut storage_manager = test_storage_manager(&env);
+
+        let base_offset = storage_manager.claim_stack_area_with_alignment(Symbol::DEV_TMP, 16, 8);
+        storage_manager.claim_stack_area_with_alignment(Symbol::DEV_TMP2, 16, 8);
+
+        // Simulate a bug that freed part of DEV_TMP while it is still alive.
+        storage_manager.free_stack_chunk(base_offset + 8, 8);
+        storage_manager.free_symbol(&Symbol::DEV_TMP2);
+    }
+
+    #[test]
+    fn tmp_reg_callback_result_is_returned() {
+        let arena = bumpalo::Bump::new();
+        let env = test_env(&arena);
+        let mut storage_manager = test_storage_manager(&env);
+        let mut buf = bumpalo::vec![in &arena];
+
+        let tmp_reg = storage_manager.with_tmp_general_reg(&mut buf, |_, _, reg| reg);
+        assert!(storage_manager.general_free_regs.contains(&tmp_reg));
+
+        let tmp_reg = storage_manager.with_tmp_float_reg(&mut buf, |_, _, reg| reg);
+        assert!(storage_manager.float_free_regs.contains(&tmp_reg));
+    }
+
+    #[test]
+    fn no_self_move_for_symbol_already_in_place() {
+        let arena = bumpalo::Bump::new();
+        let env = test_env(&arena);
+        let mut storage_manager = test_storage_manager(&env);
+        let mut buf = bumpalo::vec![in &arena];
+
+        let general_reg = storage_manager.claim_general_reg(&mut buf, &Symbol::DEV_TMP);
+        let float_reg = storage_manager.claim_float_reg(&mut buf, &Symbol::DEV_TMP2);
+        assert!(buf.is_empty());
+
+        storage_manager.load_to_specified_general_reg(&mut buf, &Symbol::DEV_TMP, general_reg);
+        storage_manager.load_to_specified_float_reg(&mut buf, &Symbol::DEV_TMP2, float_reg);
+        assert!(buf.is_empty());
+
+        let other_reg = storage_manager.claim_general_reg(&mut buf, &Symbol::DEV_TMP3);
+        storage_manager.load_to_specified_general_reg(&mut buf, &Symbol::DEV_TMP, other_reg);
+        assert!(!buf.is_empty());
+    }
+
+    #[test]
+    fn move_reg_if_needed_skips_self_moves() {
+        use X86_64FloatReg::*;
+        use X86_64GeneralReg::*;
+
+        let arena = bumpalo::Bump::new();
+        let env = test_env(&arena);
+        let storage_manager = test_storage_manager(&env);
+        let mut buf = bumpalo::vec![in &arena];
+
+        storage_manager.move_reg_if_needed(&mut buf, General(RAX), General(RAX));
+        storage_manager.move_reg_if_needed(&mut buf, Float(XMM0), Float(XMM0));
+        assert!(buf.is_empty());
+
+        storage_manager.move_reg_if_needed(&mut buf, General(RBX), General(RAX));
+        storage_manager.move_reg_if_needed(&mut buf, Float(XMM1), Float(XMM0));
+
+        let mut expected = bumpalo::vec![in &arena];
+        X86_64Assembler::mov_reg64_reg64(&mut expected, RBX, RAX);
+        X86_64Assembler::mov_freg64_freg64(&mut expected, XMM1, XMM0);
+        assert_eq!(buf, expected);
+    }
+
+    #[test]
+    fn reg_for_sym_does_not_load() {
+        let arena = bumpalo::Bump::new();
+        let env = test_env(&arena);
+        let mut storage_manager = test_storage_manager(&env);
+        let mut buf = bumpalo::vec![in &arena];
+
+        let general_reg = storage_manager.claim_general_reg(&mut buf, &Symbol::DEV_TMP);
+        let float_reg = storage_manager.claim_float_reg(&mut buf, &Symbol::DEV_TMP2);
+        storage_manager.primitive_stack_arg(&Symbol::DEV_TMP3, 16);
+
+        assert_eq!(
+            storage_manager.reg_for_sym(&Symbol::DEV_TMP),
+            Some(General(general_reg))
+        );
+        assert_eq!(
+            storage_manager.reg_for_sym(&Symbol::DEV_TMP2),
+            Some(Float(float_reg))
+        );
+        assert_eq!(storage_manager.reg_for_sym(&Symbol::DEV_TMP3), None);
+        assert!(buf.is_empty());
+    }
+
+    #[test]
+    #[should_panic(
+        expected = "completely out of general purpose registers while claiming one for Some("
+    )]
+    fn out_of_general_regs_names_symbol() {
+        let arena = bumpalo::Bump::new();
+        let env = test_env(&arena);
+        let mut storage_manager = test_storage_manager(&env);
+        let mut buf = bumpalo::vec![in &arena];
+
+        // Pretend every register is held by a temporary, those can not be spilled.
+        storage_manager.general_free_regs.clear();
+        storage_manager.claim_general_reg(&mut buf, &Symbol::DEV_TMP);
+    }
+
+    #[test]
+    fn restore_snapshot() {
+        let arena = bumpalo::Bump::new();
+        let env = test_env(&arena);
+        let mut storage_manager = test_storage_manager(&env);
+        let mut buf = bumpalo::vec![in &arena];
+
+        storage_manager.claim_general_reg(&mut buf, &Symbol::DEV_TMP);
+        storage_manager.claim_stack_area_with_alignment(Symbol::DEV_TMP2, 24, 8);
+
+        let before = storage_manager.clone();
+        let snapshot = storage_manager.snapshot();
+
+        storage_manager.claim_float_reg(&mut buf, &Symbol::DEV_TMP3);
+        storage_manager.claim_stack_area_with_alignment(Symbol::DEV_TMP4, 16, 16);
+        storage_manager.free_symbol(&Symbol::DEV_TMP);
+        storage_manager.free_symbol(&Symbol::DEV_TMP2);
+
+        storage_manager.restore(&snapshot);
+
+        assert_eq!(
+            storage_manager.symbol_storage_map,
+            before.symbol_storage_map
+        );
+        assert_eq!(storage_manager.allocation_map, before.allocation_map);
+        assert_eq!(storage_manager.general_free_regs, before.general_free_regs);
+        assert_eq!(storage_manager.float_free_regs, before.float_free_regs);
+        assert_eq!(storage_manager.general_used_regs, before.general_used_regs);
+        assert_eq!(storage_manager.float_used_regs, before.float_used_regs);
+        assert_eq!(storage_manager.free_stack_chunks, before.free_stack_chunks);
+        assert_eq!(storage_manager.stack_size, before.stack_size);
+    }
+
+    #[test]
+    fn restore_keeps_callee_saved_regs_and_fn_call_stack_size() {
+        use X86_64GeneralReg::*;
+
+        let arena = bumpalo::Bump::new();
+        let env = test_env(&arena);
+        let mut storage_manager = test_storage_manager(&env);
+
+        let snapshot = storage_manager.snapshot();
+
+        storage_manager.used_callee_saved_regs.insert_general(RBX);
+        storage_manager.update_fn_call_stack_size(32);
+
+        storage_manager.restore(&snapshot);
+
+        assert!(storage_manager
+            .used_callee_saved_regs
+            .general
+            .contains(&RBX));
+        assert_eq!(storage_manager.fn_call_stack_size(), 32);
+    }
+
+    #[test]
+    fn peak_register_pressure() {
+        let arena = bumpalo::Bump::new();
+        let env = test_env(&arena);
+        let mut storage_manager = test_storage_manager(&env);
+        let mut buf = bumpalo::vec![in &arena];
+
+        let syms = [Symbol::DEV_TMP, Symbol::DEV_TMP2, Symbol::DEV_TMP3];
+        for sym in syms.iter() {
+            storage_manager.claim_general_reg(&mut buf, sym);
+        }
+        storage_manager.claim_float_reg(&mut buf, &Symbol::DEV_TMP4);
+        for sym in syms.iter() {
+            storage_manager.free_symbol(sym);
+        }
+        storage_manager.claim_general_reg(&mut buf, &Symbol::DEV_TMP5);
+
+        assert_eq!(storage_manager.peak_general_pressure(), 3);
+        assert_eq!(storage_manager.peak_float_pressure(), 1);
+
+        storage_manager.reset();
+        assert_eq!(storage_manager.peak_general_pressure(), 0);
+        assert_eq!(storage_manager.peak_float_pressure(), 0);
+    }
+
+    #[test]
+    fn setup_call_args_swaps_registers() {
+        use capstone::prelude::*;
+        use X86_64GeneralReg::*;
+
+        let arena = bumpalo::Bump::new();
+        let env = test_env(&arena);
+        let mut storage_manager = test_storage_manager(&env);
+        let mut buf = bumpalo::vec![in &arena];
+
+        // `a` is in RDI and `b` in RSI, the call is `f(b, a)`.
+        storage_manager.general_reg_arg(&Symbol::DEV_TMP, RDI);
+        storage_manager.general_reg_arg(&Symbol::DEV_TMP2, RSI);
+
+        storage_manager
+            .setup_call_args(&mut buf, &[(Symbol::DEV_TMP2, RDI), (Symbol::DEV_TMP, RSI)]);
+
+        // Run the emitted moves on registers that start out holding their symbol.
+        let cs = Capstone::new()
+            .x86()
+            .mode(arch::x86::ArchMode::Mode64)
+            .syntax(arch::x86::ArchSyntax::Intel)
+            .build()
+            .expect("Failed to create Capstone object");
+        let mut regs =
+            std::collections::HashMap::from([("rdi".to_string(), "a"), ("rsi".to_string(), "b")]);
+        for inst in cs.disasm_all(&buf, 0).unwrap().iter() {
+            assert_eq!(
+                inst.mnemonic(),
+                Some("mov"),
+                "unexpected instruction: {inst}"
+            );
+            let op_str = inst.op_str().unwrap();
+            let (dst, src) = op_str.split_once(", ").unwrap();
+            let value = regs.get(src).copied().unwrap_or("garbage");
+            regs.insert(dst.to_string(), value);
+        }
+        assert_eq!(regs["rdi"], "b");
+        assert_eq!(regs["rsi"], "a");
+
+        // Both registers were overwritten, so the symbols now live in their argument register.
+        assert_eq!(
+            storage_manager.reg_for_sym(&Symbol::DEV_TMP),
+            Some(General(RSI))
+        );
+        assert_eq!(
+            storage_manager.reg_for_sym(&Symbol::DEV_TMP2),
+            Some(General(RDI))
+        );
+        // Nothing was spilled, the swap went through a scratch register.
+        assert_eq!(storage_manager.stack_size(), 0);
+        assert!(!storage_manager.general_free_regs.contains(&RDI));
+        assert!(!storage_manager.general_free_regs.contains(&RSI));
+    }
+
+    #[test]
+    fn no_self_moves_when_shuffling_registers() {
+        use capstone::prelude::*;
+        use X86_64GeneralReg::*;
+
+        let arena = bumpalo::Bump::new();
+        let env = test_env(&arena);
+        let mut storage_manager = test_storage_manager(&env);
+        let mut buf = bumpalo::vec![in &arena];
+
+        storage_manager.general_reg_arg(&Symbol::DEV_TMP, RDI);
+        storage_manager.general_reg_arg(&Symbol::DEV_TMP2, RSI);
+
+        // DEV_TMP is already in place, only DEV_TMP2 has to move.
+        storage_manager.load_to_specified_general_reg(&mut buf, &Symbol::DEV_TMP, RDI);
+        storage_manager.load_to_specified_general_reg(&mut buf, &Symbol::DEV_TMP2, RDX);
+        X86_64Assembler::mov_reg64_reg64(&mut buf, RCX, RCX);
+
+        let cs = Capstone::new()
+            .x86()
+            .mode(arch::x86::ArchMode::Mode64)
+            .syntax(arch::x86::ArchSyntax::Intel)
+            .build()
+            .expect("Failed to create Capstone object");
+        let instructions = cs.disasm_all(&buf, 0).unwrap();
+
+        // Only the move of DEV_TMP2 is emitted.
+        assert_eq!(instructions.len(), 1);
+        for inst in instructions.iter() {
+            let operands: std::vec::Vec<_> = inst.op_str().unwrap().split(", ").collect();
+            assert!(
+                operands.len() != 2 || operands[0] != operands[1],
+                "self move emitted: {inst}"
+            );
+        }
+    }
 }
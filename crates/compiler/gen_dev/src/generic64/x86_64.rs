@@ -1,4 +1,7 @@
-use crate::generic64::{storage::StorageManager, Assembler, CallConv, RegTrait};
+use crate::generic64::{
+    storage::{RegStorage, StorageManager},
+    Assembler, CallConv, RegTrait,
+};
 use crate::{
     pointer_layouts, single_register_floats, single_register_int_builtins,
     single_register_integers, single_register_layouts, Relocation,
@@ -324,12 +327,15 @@ impl CallConv<X86_64GeneralReg, X86_64FloatReg, X86_64Assembler> for X86_64Syste
             general_i,
             float_i: 0,
             tmp_stack_offset: Self::SHADOW_SPACE_SIZE as i32,
+            general_reg_args: Vec::new_in(buf.bump()),
         };
 
         for (sym, in_layout) in args.iter().zip(arg_layouts.iter()) {
             state.store_arg(buf, storage_manager, layout_interner, *sym, *in_layout);
         }
 
+        storage_manager.setup_call_args(buf, &state.general_reg_args);
+
         storage_manager.update_fn_call_stack_size(state.tmp_stack_offset as u32);
     }
 
@@ -654,20 +660,25 @@ pub(crate) fn copy_to_base_offset<GeneralReg, FloatReg, ASM>(
     }
 }
 
-struct X64_64SystemVStoreArgs {
+struct X64_64SystemVStoreArgs<'a> {
     general_i: usize,
     float_i: usize,
     tmp_stack_offset: i32,
+    /// Symbols that go in general argument registers. They are loaded together once every
+    /// other argument is placed, see `StorageManager::setup_call_args`.
+    /// Caller saved registers are pushed to the stack before a call, so placing the other
+    /// arguments first can not clobber these.
+    general_reg_args: Vec<'a, (Symbol, X86_64GeneralReg)>,
 }
 
-impl X64_64SystemVStoreArgs {
+impl<'a> X64_64SystemVStoreArgs<'a> {
     const GENERAL_PARAM_REGS: &'static [X86_64GeneralReg] = X86_64SystemV::GENERAL_PARAM_REGS;
     const GENERAL_RETURN_REGS: &'static [X86_64GeneralReg] = X86_64SystemV::GENERAL_RETURN_REGS;
 
     const FLOAT_PARAM_REGS: &'static [X86_64FloatReg] = X86_64SystemV::FLOAT_PARAM_REGS;
     const FLOAT_RETURN_REGS: &'static [X86_64FloatReg] = X86_64SystemV::FLOAT_RETURN_REGS;
 
-    fn store_arg<'a>(
+    fn store_arg(
         &mut self,
         buf: &mut Vec<'a, u8>,
         storage_manager: &mut X86_64StorageManager<'a, '_, X86_64SystemV>,
@@ -753,7 +764,7 @@ impl X64_64SystemVStoreArgs {
         }
     }
 
-    fn store_arg_general<'a>(
+    fn store_arg_general(
         &mut self,
         buf: &mut Vec<'a, u8>,
         storage_manager: &mut X86_64StorageManager<'a, '_, X86_64SystemV>,
@@ -761,22 +772,27 @@ impl X64_64SystemVStoreArgs {
     ) {
         match Self::GENERAL_PARAM_REGS.get(self.general_i) {
             Some(reg) => {
-                storage_manager.load_to_specified_general_reg(buf, &sym, *reg);
+                self.general_reg_args.push((sym, *reg));
                 self.general_i += 1;
             }
             None => {
-                // Copy to stack using return reg as buffer.
-                let tmp = Self::GENERAL_RETURN_REGS[0];
+                if let Some(RegStorage::General(reg)) = storage_manager.reg_for_sym(&sym) {
+                    // Already in a register, copy it to the stack directly.
+                    X86_64Assembler::mov_stack32_reg64(buf, self.tmp_stack_offset, reg);
+                } else {
+                    // Copy to stack using return reg as buffer.
+                    let tmp = Self::GENERAL_RETURN_REGS[0];
 
-                storage_manager.load_to_specified_general_reg(buf, &sym, tmp);
-                X86_64Assembler::mov_stack32_reg64(buf, self.tmp_stack_offset, tmp);
+                    storage_manager.load_to_specified_general_reg(buf, &sym, tmp);
+                    X86_64Assembler::mov_stack32_reg64(buf, self.tmp_stack_offset, tmp);
+                }
 
                 self.tmp_stack_offset += 8;
             }
         }
     }
 
-    fn store_arg_float<'a>(
+    fn store_arg_float(
         &mut self,
         buf: &mut Vec<'a, u8>,
         storage_manager: &mut X86_64StorageManager<'a, '_, X86_64SystemV>,
@@ -788,18 +804,23 @@ impl X64_64SystemVStoreArgs {
                 self.float_i += 1;
             }
             None => {
-                // Copy to stack using return reg as buffer.
-                let tmp = Self::FLOAT_RETURN_REGS[0];
+                if let Some(RegStorage::Float(reg)) = storage_manager.reg_for_sym(&sym) {
+                    // Already in a register, copy it to the stack directly.
+                    X86_64Assembler::mov_stack32_freg64(buf, self.tmp_stack_offset, reg);
+                } else {
+                    // Copy to stack using return reg as buffer.
+                    let tmp = Self::FLOAT_RETURN_REGS[0];
 
-                storage_manager.load_to_specified_float_reg(buf, &sym, tmp);
-                X86_64Assembler::mov_stack32_freg64(buf, self.tmp_stack_offset, tmp);
+                    storage_manager.load_to_specified_float_reg(buf, &sym, tmp);
+                    X86_64Assembler::mov_stack32_freg64(buf, self.tmp_stack_offset, tmp);
+                }
 
                 self.tmp_stack_offset += 8;
             }
         }
     }
 
-    fn store_arg_64bit<'a>(
+    fn store_arg_64bit(
         &mut self,
         buf: &mut Vec<'a, u8>,
         storage_manager: &mut X86_64StorageManager<'a, '_, X86_64SystemV>,
@@ -827,7 +848,7 @@ impl X64_64SystemVStoreArgs {
         }
     }
 
-    fn store_arg_128bit<'a>(
+    fn store_arg_128bit(
         &mut self,
         buf: &mut Vec<'a, u8>,
         storage_manager: &mut X86_64StorageManager<'a, '_, X86_64SystemV>,
@@ -860,13 +881,18 @@ impl X64_64SystemVStoreArgs {
     }
 }
 
-struct X64_64WindowsFastCallStoreArgs {
+struct X64_64WindowsFastCallStoreArgs<'a> {
     general_i: usize,
     float_i: usize,
     tmp_stack_offset: i32,
+    /// Symbols that go in general argument registers. They are loaded together once every
+    /// other argument is placed, see `StorageManager::setup_call_args`.
+    /// Caller saved registers are pushed to the stack before a call, so placing the other
+    /// arguments first can not clobber these.
+    general_reg_args: Vec<'a, (Symbol, X86_64GeneralReg)>,
 }
 
-impl X64_64WindowsFastCallStoreArgs {
+impl<'a> X64_64WindowsFastCallStoreArgs<'a> {
     const GENERAL_PARAM_REGS: &'static [X86_64GeneralReg] =
         X86_64WindowsFastcall::GENERAL_PARAM_REGS;
     const GENERAL_RETURN_REGS: &'static [X86_64GeneralReg] =
@@ -875,7 +901,7 @@ impl X64_64WindowsFastCallStoreArgs {
     const FLOAT_PARAM_REGS: &'static [X86_64FloatReg] = X86_64WindowsFastcall::FLOAT_PARAM_REGS;
     const FLOAT_RETURN_REGS: &'static [X86_64FloatReg] = X86_64WindowsFastcall::FLOAT_RETURN_REGS;
 
-    fn store_arg<'a>(
+    fn store_arg(
         &mut self,
         buf: &mut Vec<'a, u8>,
         storage_manager: &mut X86_64StorageManager<'a, '_, X86_64WindowsFastcall>,
@@ -977,7 +1003,7 @@ impl X64_64WindowsFastCallStoreArgs {
         }
     }
 
-    fn store_arg_general<'a>(
+    fn store_arg_general(
         &mut self,
         buf: &mut Vec<'a, u8>,
         storage_manager: &mut X86_64StorageManager<'a, '_, X86_64WindowsFastcall>,
@@ -985,22 +1011,27 @@ impl X64_64WindowsFastCallStoreArgs {
     ) {
         match Self::GENERAL_PARAM_REGS.get(self.general_i) {
             Some(reg) => {
-                storage_manager.load_to_specified_general_reg(buf, &sym, *reg);
+                self.general_reg_args.push((sym, *reg));
                 self.general_i += 1;
             }
             None => {
-                // Copy to stack using return reg as buffer.
-                let tmp = Self::GENERAL_RETURN_REGS[0];
+                if let Some(RegStorage::General(reg)) = storage_manager.reg_for_sym(&sym) {
+                    // Already in a register, copy it to the stack directly.
+                    X86_64Assembler::mov_stack32_reg64(buf, self.tmp_stack_offset, reg);
+                } else {
+                    // Copy to stack using return reg as buffer.
+                    let tmp = Self::GENERAL_RETURN_REGS[0];
 
-                storage_manager.load_to_specified_general_reg(buf, &sym, tmp);
-                X86_64Assembler::mov_stack32_reg64(buf, self.tmp_stack_offset, tmp);
+                    storage_manager.load_to_specified_general_reg(buf, &sym, tmp);
+                    X86_64Assembler::mov_stack32_reg64(buf, self.tmp_stack_offset, tmp);
+                }
 
                 self.tmp_stack_offset += 8;
             }
         }
     }
 
-    fn store_arg_float<'a>(
+    fn store_arg_float(
         &mut self,
         buf: &mut Vec<'a, u8>,
         storage_manager: &mut X86_64StorageManager<'a, '_, X86_64WindowsFastcall>,
@@ -1012,11 +1043,16 @@ impl X64_64WindowsFastCallStoreArgs {
                 self.float_i += 1;
             }
             None => {
-                // Copy to stack using return reg as buffer.
-                let tmp = Self::FLOAT_RETURN_REGS[0];
+                if let Some(RegStorage::Float(reg)) = storage_manager.reg_for_sym(&sym) {
+                    // Already in a register, copy it to the stack directly.
+                    X86_64Assembler::mov_stack32_freg64(buf, self.tmp_stack_offset, reg);
+                } else {
+                    // Copy to stack using return reg as buffer.
+                    let tmp = Self::FLOAT_RETURN_REGS[0];
 
-                storage_manager.load_to_specified_float_reg(buf, &sym, tmp);
-                X86_64Assembler::mov_stack32_freg64(buf, self.tmp_stack_offset, tmp);
+                    storage_manager.load_to_specified_float_reg(buf, &sym, tmp);
+                    X86_64Assembler::mov_stack32_freg64(buf, self.tmp_stack_offset, tmp);
+                }
 
                 self.tmp_stack_offset += 8;
             }
@@ -1551,12 +1587,15 @@ impl CallConv<X86_64GeneralReg, X86_64FloatReg, X86_64Assembler> for X86_64Windo
             general_i,
             float_i: 0,
             tmp_stack_offset: Self::SHADOW_SPACE_SIZE as i32,
+            general_reg_args: Vec::new_in(buf.bump()),
         };
 
         for (sym, in_layout) in args.iter().zip(arg_layouts.iter()) {
             state.store_arg(buf, storage_manager, layout_interner, *sym, *in_layout);
         }
 
+        storage_manager.setup_call_args(buf, &state.general_reg_args);
+
         storage_manager.update_fn_call_stack_size(state.tmp_stack_offset as u32);
     }
 
@@ -5233,4 +5272,101 @@ mod tests {
             ALL_GENERAL_REGS
         );
     }
+
+    #[test]
+    fn store_stack_arg_straight_from_its_reg() {
+        use crate::generic64::storage::new_storage_manager;
+        use crate::{AssemblyBackendMode, Env};
+        use roc_collections::all::MutSet;
+        use roc_module::symbol::ModuleId;
+        use roc_target::TargetInfo;
+
+        let arena = bumpalo::Bump::new();
+        let env = Env {
+            arena: &arena,
+            module_id: ModuleId::ATTR,
+            exposed_to_host: MutSet::default(),
+            lazy_literals: false,
+            mode: AssemblyBackendMode::Test,
+        };
+        let mut storage_manager: X86_64StorageManager<'_, '_, X86_64SystemV> =
+            new_storage_manager(&env, TargetInfo::default_x86_64());
+        storage_manager.reset();
+        storage_manager.general_reg_arg(&Symbol::DEV_TMP, X86_64GeneralReg::RBX);
+        storage_manager.float_reg_arg(&Symbol::DEV_TMP2, X86_64FloatReg::XMM8);
+
+        // Every argument register is taken, so both symbols go on the stack.
+        let mut state = X64_64SystemVStoreArgs {
+            general_i: X64_64SystemVStoreArgs::GENERAL_PARAM_REGS.len(),
+            float_i: X64_64SystemVStoreArgs::FLOAT_PARAM_REGS.len(),
+            tmp_stack_offset: 0,
+            general_reg_args: bumpalo::vec![in &arena],
+        };
+        let mut buf = bumpalo::vec![in &arena];
+        state.store_arg_general(&mut buf, &mut storage_manager, Symbol::DEV_TMP);
+        state.store_arg_float(&mut buf, &mut storage_manager, Symbol::DEV_TMP2);
+
+        let mut expected = bumpalo::vec![in &arena];
+        X86_64Assembler::mov_stack32_reg64(&mut expected, 0, X86_64GeneralReg::RBX);
+        X86_64Assembler::mov_stack32_freg64(&mut expected, 8, X86_64FloatReg::XMM8);
+        assert_eq!(buf, expected);
+    }
+
+    #[test]
+    fn store_args_swaps_arg_registers() {
+        use crate::generic64::storage::new_storage_manager;
+        use crate::{AssemblyBackendMode, Env};
+        use roc_collections::all::MutSet;
+        use roc_module::symbol::ModuleId;
+        use roc_mono::layout::Layout;
+        use roc_target::TargetInfo;
+        use X86_64GeneralReg::*;
+
+        let arena = bumpalo::Bump::new();
+        let env = Env {
+            arena: &arena,
+            module_id: ModuleId::ATTR,
+            exposed_to_host: MutSet::default(),
+            lazy_literals: false,
+            mode: AssemblyBackendMode::Test,
+        };
+        let mut layout_interner = STLayoutInterner::with_capacity(4, TargetInfo::default_x86_64());
+        let mut storage_manager: X86_64StorageManager<'_, '_, X86_64SystemV> =
+            new_storage_manager(&env, TargetInfo::default_x86_64());
+        storage_manager.reset();
+
+        // `a` is in RDI and `b` in RSI, the call is `f(b, a)`.
+        let a = Symbol::DEV_TMP;
+        let b = Symbol::DEV_TMP2;
+        storage_manager.general_reg_arg(&a, RDI);
+        storage_manager.general_reg_arg(&b, RSI);
+
+        let (mut buf, cs) = setup_capstone_and_arena(&arena);
+        X86_64SystemV::store_args(
+            &mut buf,
+            &mut storage_manager,
+            &mut layout_interner,
+            &Symbol::DEV_TMP3,
+            &[b, a],
+            &[Layout::I64, Layout::I64],
+            &Layout::I64,
+        );
+
+        // Run the emitted moves on registers that start out holding their symbol.
+        let mut regs =
+            std::collections::HashMap::from([("rdi".to_string(), "a"), ("rsi".to_string(), "b")]);
+        for inst in cs.disasm_all(&buf, 0).unwrap().iter() {
+            assert_eq!(
+                inst.mnemonic(),
+                Some("mov"),
+                "unexpected instruction: {inst}"
+            );
+            let op_str = inst.op_str().unwrap();
+            let (dst, src) = op_str.split_once(", ").unwrap();
+            let value = regs.get(src).copied().unwrap_or("garbage");
+            regs.insert(dst.to_string(), value);
+        }
+        assert_eq!(regs["rdi"], "b");
+        assert_eq!(regs["rsi"], "a");
+    }
 }
@@ -7,7 +7,9 @@ use crate::blankspace::{
     space0_after_e, space0_around_e_no_after_indent_check, space0_around_ee, space0_before_e,
     space0_before_optional_after, space0_e, spaces, spaces_around, spaces_before,
 };
-use crate::ident::{integer_ident, lowercase_ident, parse_ident, Accessor, Ident};
+use crate::ident::{
+    integer_ident, lowercase_ident, parse_ident, record_field_name, Accessor, Ident,
+};
 use crate::keyword;
 use crate::parser::{
     self, backtrackable, increment_min_indent, line_min_indent, optional, reset_min_indent,
@@ -2442,28 +2444,28 @@ fn if_expr_help<'a>(options: ExprParseOptions) -> impl Parser<'a, Expr<'a>, EIf<
     }
 }
 
+/// `par` is a contextual keyword, it only starts a par expression when followed by a tuple.
+/// Otherwise nothing is consumed, so it can be parsed as a regular identifier instead.
 fn par_expr_help<'a>() -> impl Parser<'a, Expr<'a>, EPar<'a>> {
     move |arena: &'a Bump, state: State<'a>, min_indent| {
+        let start = state.pos();
         let (_, _, state) =
             parser::keyword_e(keyword::PAR, EPar::Par).parse(arena, state, min_indent)?;
 
-        println!("par keyword parsed");
-
-        let (_, body, state) = space0_before_e(
-            specialize_ref(EPar::BadTuple, loc_expr_in_parens_help()),
-            EPar::Indent,
-        )
-        .parse(arena, state, min_indent)
-        .map_err(|(_, f)| (MadeProgress, f))?;
+        // Look past any spaces, newlines and comments, the same way the tuple itself is parsed.
+        let state = match space0_e(EPar::Indent).parse(arena, state, min_indent) {
+            Ok((_, _, state)) if state.bytes().first() == Some(&b'(') => state,
+            _ => return Err((NoProgress, EPar::Par(start))),
+        };
 
-        println!("good expr");
+        let (_, body, state) = specialize_ref(EPar::BadTuple, loc_expr_in_parens_help())
+            .parse(arena, state, min_indent)
+            .map_err(|(_, f)| (MadeProgress, f))?;
 
         let Expr::Tuple(body_exprs) = body.value else {
             return Err((NoProgress, EPar::NotATuple(state.pos())));
         };
 
-        println!("good tuple");
-
         Ok((MadeProgress, Expr::Par(body_exprs), state))
     }
 }
@@ -2688,7 +2690,7 @@ pub fn record_field<'a>() -> impl Parser<'a, RecordField<'a>, ERecord<'a>> {
 
     map_with_arena!(
         and!(
-            specialize(|_, pos| ERecord::Field(pos), loc!(lowercase_ident())),
+            specialize(|_, pos| ERecord::Field(pos), loc!(record_field_name())),
             and!(
                 spaces(),
                 optional(either!(
@@ -1,5 +1,6 @@
+use crate::blankspace::spaces;
 use crate::parser::Progress::{self, *};
-use crate::parser::{BadInputError, EExpr, ParseResult, Parser};
+use crate::parser::{BadInputError, EExpr, ERecord, ParseResult, Parser};
 use crate::state::State;
 use bumpalo::collections::vec::Vec;
 use bumpalo::Bump;
@@ -87,7 +88,7 @@ pub fn lowercase_ident<'a>() -> impl Parser<'a, &'a str, ()> {
     move |_, state: State<'a>, _min_indent: u32| match chomp_lowercase_part(state.bytes()) {
         Err(progress) => Err((progress, ())),
         Ok(ident) => {
-            if crate::keyword::KEYWORDS.iter().any(|kw| &ident == kw) {
+            if crate::keyword::is_keyword(ident) {
                 Err((NoProgress, ()))
             } else {
                 let width = ident.len();
@@ -97,6 +98,32 @@ pub fn lowercase_ident<'a>() -> impl Parser<'a, &'a str, ()> {
     }
 }
 
+/// Like `lowercase_ident`, but keywords are allowed if they are given a value
+/// with `:` or `?`, e.g. the "when" in `{ when: 1 }`.
+/// Any spaces, newlines and comments between the name and the `:` or `?` are skipped.
+pub fn record_field_name<'a>() -> impl Parser<'a, &'a str, ()> {
+    move |arena, state: State<'a>, min_indent: u32| match chomp_lowercase_part(state.bytes()) {
+        Err(progress) => Err((progress, ())),
+        Ok(ident) => {
+            let width = ident.len();
+            let state = state.advance(width);
+
+            let has_value = match spaces::<ERecord<'a>>().parse(arena, state.clone(), min_indent) {
+                Ok((_, _, after_spaces)) => {
+                    matches!(after_spaces.bytes().first(), Some(b':' | b'?'))
+                }
+                Err(_) => false,
+            };
+
+            if crate::keyword::allowed_as_field_name(ident, has_value) {
+                Ok((MadeProgress, ident, state))
+            } else {
+                Err((NoProgress, ()))
+            }
+        }
+    }
+}
+
 /// This is a tuple accessor, e.g. "1" in `.1`
 pub fn integer_ident<'a>() -> impl Parser<'a, &'a str, ()> {
     move |_, state: State<'a>, _min_indent: u32| match chomp_integer_part(state.bytes()) {
@@ -114,7 +141,7 @@ pub fn lowercase_ident_keyword_e<'a>() -> impl Parser<'a, &'a str, ()> {
     move |_, state: State<'a>, _min_indent: u32| match chomp_lowercase_part(state.bytes()) {
         Err(progress) => Err((progress, ())),
         Ok(ident) => {
-            if crate::keyword::KEYWORDS.iter().any(|kw| &ident == kw) {
+            if crate::keyword::is_keyword(ident) {
                 Err((MadeProgress, ()))
             } else {
                 let width = ident.len();
@@ -164,7 +191,7 @@ pub fn unqualified_ident<'a>() -> impl Parser<'a, &'a str, ()> {
     move |_, state: State<'a>, _min_indent: u32| match chomp_anycase_part(state.bytes()) {
         Err(progress) => Err((progress, ())),
         Ok(ident) => {
-            if crate::keyword::KEYWORDS.iter().any(|kw| &ident == kw) {
+            if crate::keyword::is_keyword(ident) {
                 Err((MadeProgress, ()))
             } else {
                 let width = ident.len();
@@ -192,11 +219,9 @@ pub fn parse_ident<'a>(
             let state = advance_state!(state, width as usize)?;
             if let Ident::Access { module_name, parts } = ident {
                 if module_name.is_empty() {
-                    if let Some(first) = parts.first() {
-                        for keyword in crate::keyword::KEYWORDS.iter() {
-                            if first == &Accessor::RecordField(keyword) {
-                                return Err((NoProgress, EExpr::Start(initial.pos())));
-                            }
+                    if let Some(Accessor::RecordField(first)) = parts.first() {
+                        if crate::keyword::is_keyword(first) {
+                            return Err((NoProgress, EExpr::Start(initial.pos())));
                         }
                     }
                 }
@@ -271,6 +296,17 @@ pub enum BadIdent {
     QualifiedTupleAccessor(Position),
 }
 
+/// If `bytes` starts with a lowercase identifier that looks like a misspelled keyword (like `esle`),
+/// returns the region of that identifier, given that it starts at `pos`, and the keyword.
+pub fn misspelled_keyword(bytes: &[u8], pos: Position) -> Option<(Region, &'static str)> {
+    let ident = chomp_lowercase_part(bytes).ok()?;
+    let keyword = crate::keyword::closest_keyword(ident)?;
+
+    let region = Region::new(pos, pos.bump_column(ident.len() as u32));
+
+    Some((region, keyword))
+}
+
 fn is_alnum(ch: char) -> bool {
     ch.is_alphabetic() || ch.is_ascii_digit()
 }
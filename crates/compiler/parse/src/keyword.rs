@@ -1,20 +1,175 @@
-// These keywords are valid in expressions
-pub const IF: &str = "if";
-pub const THEN: &str = "then";
-pub const ELSE: &str = "else";
-pub const WHEN: &str = "when";
-pub const AS: &str = "as";
-pub const IS: &str = "is";
-pub const DBG: &str = "dbg";
-pub const EXPECT: &str = "expect";
-pub const EXPECT_FX: &str = "expect-fx";
-pub const CRASH: &str = "crash";
-pub const PAR: &str = "par";
-
-// These keywords are valid in types
-pub const IMPLEMENTS: &str = "implements";
-pub const WHERE: &str = "where";
-
-pub const KEYWORDS: [&str; 11] = [
-    IF, THEN, ELSE, WHEN, AS, IS, DBG, EXPECT, EXPECT_FX, CRASH, PAR,
-];
+/// Declares every keyword exactly once. This generates its constant, its `Keyword` variant,
+/// and the lists it belongs to, so that none of them can get out of sync.
+macro_rules! keywords {
+    (
+        reserved { $($reserved:ident = $reserved_str:literal => $reserved_variant:ident,)* }
+        contextual { $($contextual:ident = $contextual_str:literal => $contextual_variant:ident,)* }
+        types { $($type_kw:ident = $type_str:literal => $type_variant:ident,)* }
+    ) => {
+        $(pub const $reserved: &str = $reserved_str;)*
+        $(pub const $contextual: &str = $contextual_str;)*
+        $(pub const $type_kw: &str = $type_str;)*
+
+        pub const EXPR_KEYWORDS: &[&str] = &[$($reserved,)* $($contextual,)*];
+
+        pub const TYPE_KEYWORDS: &[&str] = &[$($type_kw,)*];
+
+        /// Contextual keywords are only special in the one position they introduce.
+        /// Everywhere else, they are parsed as plain identifiers.
+        pub const CONTEXTUAL_KEYWORDS: &[&str] = &[$($contextual,)*];
+
+        /// The keywords that are reserved everywhere.
+        /// Type keywords are only special inside of types, so they can still be used as value identifiers.
+        pub const KEYWORDS: &[&str] = &[$($reserved,)*];
+
+        /// Every keyword, so that parser code can `match` on them exhaustively instead of comparing strings.
+        #[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+        pub enum Keyword {
+            $($reserved_variant,)*
+            $($contextual_variant,)*
+            $($type_variant,)*
+        }
+
+        impl Keyword {
+            #[allow(clippy::should_implement_trait)]
+            pub fn from_str(s: &str) -> Option<Keyword> {
+                match s {
+                    $($reserved => Some(Keyword::$reserved_variant),)*
+                    $($contextual => Some(Keyword::$contextual_variant),)*
+                    $($type_kw => Some(Keyword::$type_variant),)*
+                    _ => None,
+                }
+            }
+
+            pub fn as_str(self) -> &'static str {
+                match self {
+                    $(Keyword::$reserved_variant => $reserved,)*
+                    $(Keyword::$contextual_variant => $contextual,)*
+                    $(Keyword::$type_variant => $type_kw,)*
+                }
+            }
+        }
+    };
+}
+
+keywords! {
+    // These keywords are valid in expressions, and can not be used as identifiers
+    reserved {
+        IF = "if" => If,
+        THEN = "then" => Then,
+        ELSE = "else" => Else,
+        WHEN = "when" => When,
+        AS = "as" => As,
+        IS = "is" => Is,
+        DBG = "dbg" => Dbg,
+        EXPECT = "expect" => Expect,
+        EXPECT_FX = "expect-fx" => ExpectFx,
+        CRASH = "crash" => Crash,
+    }
+    // These keywords are valid in expressions, but are identifiers everywhere else
+    contextual {
+        PAR = "par" => Par,
+    }
+    // These keywords are valid in types
+    types {
+        IMPLEMENTS = "implements" => Implements,
+        WHERE = "where" => Where,
+    }
+}
+
+/// Words that may become keywords in the future.
+/// They can still be used as identifiers, but the parser can warn about them.
+/// This is empty for now, add a word here before turning it into a keyword.
+pub const SOFT_RESERVED: &[&str] = &[];
+
+/// What a keyword is used for, so that editors can highlight each kind differently.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum KeywordCategory {
+    /// Keywords that build up conditionals and pattern matches, like `if` and `when`.
+    ControlFlow,
+    /// Keywords that start a statement of their own, like `expect` and `dbg`.
+    Declaration,
+    /// Keywords that are only valid in types, like `implements` and `where`.
+    Type,
+}
+
+pub fn all_keywords_with_category() -> &'static [(&'static str, KeywordCategory)] {
+    use KeywordCategory::*;
+
+    &[
+        (IF, ControlFlow),
+        (THEN, ControlFlow),
+        (ELSE, ControlFlow),
+        (WHEN, ControlFlow),
+        (AS, ControlFlow),
+        (IS, ControlFlow),
+        (PAR, ControlFlow),
+        (DBG, Declaration),
+        (EXPECT, Declaration),
+        (EXPECT_FX, Declaration),
+        (CRASH, Declaration),
+        (IMPLEMENTS, Type),
+        (WHERE, Type),
+    ]
+}
+
+/// Returns true if the string is a keyword reserved in expressions,
+/// which means it can not be used as an identifier.
+pub fn is_keyword(s: &str) -> bool {
+    KEYWORDS.contains(&s)
+}
+
+pub fn is_expr_keyword(s: &str) -> bool {
+    EXPR_KEYWORDS.contains(&s)
+}
+
+pub fn is_type_keyword(s: &str) -> bool {
+    TYPE_KEYWORDS.contains(&s)
+}
+
+/// Returns the keyword the string is most likely a misspelling of, if any.
+/// Only keywords within a small edit distance are suggested, and nothing is suggested for a keyword.
+pub fn closest_keyword(s: &str) -> Option<&'static str> {
+    if is_expr_keyword(s) || is_type_keyword(s) {
+        return None;
+    }
+
+    // Short words are close to too many things to make a useful suggestion.
+    let max_distance = (s.len() / 2).min(2);
+
+    EXPR_KEYWORDS
+        .iter()
+        .chain(TYPE_KEYWORDS.iter())
+        .map(|keyword| (*keyword, distance::damerau_levenshtein(s, keyword)))
+        .filter(|(_, distance)| *distance <= max_distance)
+        .min_by_key(|(_, distance)| *distance)
+        .map(|(keyword, _)| keyword)
+}
+
+/// Returns true if the string can be the name of a record field.
+/// It must be a lowercase identifier, which rules out keywords like `expect-fx`.
+/// Other keywords are allowed only when the field is given a value (like `when` in `{ when: 1 }`),
+/// since that can't be confused with the keyword.
+/// Fields without a value (like `{ when }`) are still a variable and must not be keywords.
+pub fn allowed_as_field_name(s: &str, has_value: bool) -> bool {
+    let mut chars = s.chars();
+    let is_lowercase_ident = matches!(chars.next(), Some(first) if first.is_lowercase())
+        && chars.all(char::is_alphanumeric);
+
+    is_lowercase_ident && (has_value || !is_keyword(s))
+}
+
+/// Returns true if the string is not a keyword yet, but may become one.
+pub fn is_soft_reserved(s: &str) -> bool {
+    SOFT_RESERVED.contains(&s)
+}
+
+/// Returns the keyword the string would be if it was lowercase, like `if` for `If`.
+/// Keywords that are already written correctly return `None`.
+pub fn is_keyword_ignore_ascii_case(s: &str) -> Option<&'static str> {
+    EXPR_KEYWORDS
+        .iter()
+        .chain(TYPE_KEYWORDS.iter())
+        .find(|keyword| s != **keyword && s.eq_ignore_ascii_case(keyword))
+        .copied()
+}
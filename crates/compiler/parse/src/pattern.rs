@@ -1,6 +1,6 @@
 use crate::ast::{Implements, Pattern, PatternAs, Spaceable};
 use crate::blankspace::{space0_e, spaces, spaces_before};
-use crate::ident::{lowercase_ident, parse_ident, Accessor, Ident};
+use crate::ident::{lowercase_ident, parse_ident, record_field_name, Accessor, Ident};
 use crate::keyword;
 use crate::parser::Progress::{self, *};
 use crate::parser::{
@@ -385,8 +385,8 @@ fn loc_ident_pattern_help<'a>(
                 // Plain identifiers (e.g. `foo`) are allowed in patterns, but
                 // more complex ones (e.g. `Foo.bar` or `foo.bar.baz`) are not.
 
-                for keyword in crate::keyword::KEYWORDS.iter() {
-                    if parts[0] == Accessor::RecordField(keyword) {
+                if let Accessor::RecordField(first) = parts[0] {
+                    if crate::keyword::is_keyword(first) {
                         return Err((NoProgress, EPattern::End(original_state.pos())));
                     }
                 }
@@ -484,7 +484,7 @@ fn record_pattern_field<'a>() -> impl Parser<'a, Loc<Pattern<'a>>, PRecord<'a>>
         let pos = state.pos();
         let (progress, loc_label, state) = loc!(specialize(
             move |_, _| PRecord::Field(pos),
-            lowercase_ident()
+            record_field_name()
         ))
         .parse(arena, state, min_indent)?;
         debug_assert_eq!(progress, MadeProgress);
@@ -6,7 +6,7 @@ use crate::blankspace::{
     space0_around_ee, space0_before_e, space0_before_optional_after, space0_e,
 };
 use crate::expr::{record_field, FoundApplyValue};
-use crate::ident::{lowercase_ident, lowercase_ident_keyword_e};
+use crate::ident::{lowercase_ident, record_field_name};
 use crate::keyword;
 use crate::parser::{
     absolute_column_min_indent, increment_min_indent, then, ERecord, ETypeAbilityImpl,
@@ -309,7 +309,7 @@ fn record_type_field<'a>() -> impl Parser<'a, AssignedField<'a, TypeAnnotation<'
         let pos = state.pos();
         let (progress, loc_label, state) = loc!(specialize(
             move |_, _| ETypeRecord::Field(pos),
-            lowercase_ident_keyword_e()
+            record_field_name()
         ))
         .parse(arena, state, min_indent)?;
         debug_assert_eq!(progress, MadeProgress);
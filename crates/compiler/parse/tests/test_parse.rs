@@ -338,6 +338,233 @@ mod test_parse {
         );
     }
 
+    #[test]
+    fn keywords_are_not_identifiers() {
+        for keyword in ["if", "then", "else", "when", "is", "crash"] {
+            assert!(roc_parse::keyword::is_keyword(keyword));
+            assert_parsing_fails(
+                &format!("{keyword} = 1\n\n{keyword}"),
+                SyntaxError::Unexpected(Region::zero()),
+            );
+        }
+    }
+
+    #[test]
+    fn keyword_prefix_is_an_identifier() {
+        assert!(!roc_parse::keyword::is_keyword("iff"));
+        assert_parses_to(
+            "iff",
+            Var {
+                module_name: "",
+                ident: "iff",
+            },
+        );
+    }
+
+    #[test]
+    fn type_keywords_are_value_identifiers() {
+        use roc_parse::keyword::{is_expr_keyword, is_type_keyword};
+
+        for keyword in ["where", "implements"] {
+            assert!(is_type_keyword(keyword));
+            assert!(!is_expr_keyword(keyword));
+            assert_parses_to(
+                keyword,
+                Var {
+                    module_name: "",
+                    ident: keyword,
+                },
+            );
+        }
+
+        assert!(is_expr_keyword("when"));
+        assert!(!is_type_keyword("when"));
+    }
+
+    #[test]
+    fn closest_keyword() {
+        use roc_parse::keyword::closest_keyword;
+
+        assert_eq!(closest_keyword("esle"), Some("else"));
+        assert_eq!(closest_keyword("wehn"), Some("when"));
+        assert_eq!(closest_keyword("whene"), Some("when"));
+        assert_eq!(closest_keyword("wheer"), Some("where"));
+        assert_eq!(closest_keyword("banana"), None);
+        assert_eq!(closest_keyword("x"), None);
+        assert_eq!(closest_keyword("if"), None);
+    }
+
+    #[test]
+    fn misspelled_keyword_region() {
+        use roc_parse::ident::misspelled_keyword;
+        use roc_region::all::Position;
+
+        let pos = Position::new(10);
+
+        assert_eq!(
+            misspelled_keyword(b"esle 3", pos),
+            Some((Region::new(pos, Position::new(14)), "else"))
+        );
+        assert_eq!(misspelled_keyword(b"banana", pos), None);
+        assert_eq!(misspelled_keyword(b"else", pos), None);
+    }
+
+    #[test]
+    fn par_is_an_identifier_outside_of_par_expressions() {
+        assert!(!roc_parse::keyword::is_keyword("par"));
+
+        let arena = Bump::new();
+        let actual = parse_expr_with(&arena, "par = 3\n\npar");
+        assert!(actual.is_ok(), "{actual:?}");
+    }
+
+    #[test]
+    fn par_expression() {
+        let arena = Bump::new();
+        let actual = parse_expr_with(&arena, "par (a, b)");
+        assert!(matches!(actual, Ok(Expr::Par(items)) if items.len() == 2));
+    }
+
+    #[test]
+    fn par_expression_after_newline() {
+        let arena = Bump::new();
+        let actual = parse_expr_with(&arena, "par\n(a, b)");
+        assert!(matches!(actual, Ok(Expr::Par(items)) if items.len() == 2));
+    }
+
+    #[test]
+    fn par_expression_after_comment() {
+        let arena = Bump::new();
+        let actual = parse_expr_with(&arena, "par # c\n(a, b)");
+        assert!(matches!(actual, Ok(Expr::Par(items)) if items.len() == 2));
+    }
+
+    #[test]
+    fn par_followed_by_tab_is_not_a_par_expression() {
+        let arena = Bump::new();
+        let actual = parse_expr_with(&arena, "par\t(a, b)");
+        assert!(actual.is_err(), "{actual:?}");
+    }
+
+    #[test]
+    fn every_keyword_has_one_category() {
+        use roc_parse::keyword::*;
+
+        let categorized = all_keywords_with_category();
+        let keywords = [
+            IF, THEN, ELSE, WHEN, AS, IS, DBG, EXPECT, EXPECT_FX, CRASH, PAR, IMPLEMENTS, WHERE,
+        ];
+
+        assert_eq!(categorized.len(), keywords.len());
+        for keyword in keywords {
+            let count = categorized.iter().filter(|(kw, _)| *kw == keyword).count();
+            assert_eq!(count, 1, "{keyword} should be categorized exactly once");
+        }
+
+        for (keyword, category) in categorized {
+            assert_eq!(
+                *category == KeywordCategory::Type,
+                TYPE_KEYWORDS.contains(keyword)
+            );
+        }
+    }
+
+    #[test]
+    fn keyword_as_record_field_name() {
+        use roc_parse::keyword::allowed_as_field_name;
+
+        assert!(allowed_as_field_name("when", true));
+        assert!(!allowed_as_field_name("when", false));
+        assert!(allowed_as_field_name("email", false));
+        assert!(!allowed_as_field_name("expect-fx", true));
+        assert!(!allowed_as_field_name("Email", true));
+
+        let arena = Bump::new();
+        let actual = parse_expr_with(&arena, "{ when: 1, is: 2 }");
+        assert!(matches!(actual, Ok(Expr::Record(fields)) if fields.len() == 2));
+
+        // Without a value, the field would refer to a variable named `when`.
+        let actual = parse_expr_with(&arena, "{ when }");
+        assert!(actual.is_err());
+    }
+
+    #[test]
+    fn keyword_as_record_field_name_before_newline_and_comment() {
+        let arena = Bump::new();
+        let actual = parse_expr_with(&arena, "{ when\n    : 1 }");
+        assert!(matches!(actual, Ok(Expr::Record(fields)) if fields.len() == 1));
+
+        let actual = parse_expr_with(&arena, "{ when # the condition\n    : 1 }");
+        assert!(matches!(actual, Ok(Expr::Record(fields)) if fields.len() == 1));
+    }
+
+    #[test]
+    fn keyword_as_record_pattern_field_name() {
+        let arena = Bump::new();
+        let actual = parse_expr_with(&arena, "\\{ when: x } -> x");
+        assert!(actual.is_ok());
+
+        let actual = parse_expr_with(&arena, "\\{ when } -> when");
+        assert!(actual.is_err());
+    }
+
+    #[test]
+    fn keyword_as_record_type_field_name() {
+        let arena = Bump::new();
+        let actual = parse_expr_with(&arena, "x : { when : Str, is ? U8 }\nx = 1\n\nx");
+        assert!(actual.is_ok());
+
+        let actual = parse_expr_with(&arena, "x : { when }\nx = 1\n\nx");
+        assert!(actual.is_err());
+    }
+
+    #[test]
+    fn keyword_enum_round_trip() {
+        use roc_parse::keyword::{Keyword, EXPR_KEYWORDS, TYPE_KEYWORDS};
+
+        for keyword in EXPR_KEYWORDS.iter().chain(TYPE_KEYWORDS.iter()) {
+            let parsed = Keyword::from_str(keyword).unwrap();
+            assert_eq!(parsed.as_str(), *keyword);
+        }
+
+        assert_eq!(Keyword::from_str("expect-fx"), Some(Keyword::ExpectFx));
+        assert_eq!(Keyword::from_str("iff"), None);
+        assert_eq!(Keyword::from_str("If"), None);
+    }
+
+    #[test]
+    fn soft_reserved_words_are_still_identifiers() {
+        use roc_parse::keyword::{is_keyword, is_soft_reserved, SOFT_RESERVED};
+
+        for word in SOFT_RESERVED {
+            assert!(!is_keyword(word), "{word} is already a keyword");
+            assert!(is_soft_reserved(word));
+        }
+
+        // Nothing is soft reserved yet.
+        assert!(!is_soft_reserved("return"));
+        assert!(!is_soft_reserved("match"));
+        assert!(!is_soft_reserved("if"));
+        assert_parses_to(
+            "match",
+            Var {
+                module_name: "",
+                ident: "match",
+            },
+        );
+    }
+
+    #[test]
+    fn capitalized_keywords() {
+        use roc_parse::keyword::is_keyword_ignore_ascii_case;
+
+        assert_eq!(is_keyword_ignore_ascii_case("If"), Some("if"));
+        assert_eq!(is_keyword_ignore_ascii_case("WHEN"), Some("when"));
+        assert_eq!(is_keyword_ignore_ascii_case("Expect-Fx"), Some("expect-fx"));
+        assert_eq!(is_keyword_ignore_ascii_case("if"), None);
+        assert_eq!(is_keyword_ignore_ascii_case("Iff"), None);
+    }
+
     // PARSE ERROR
 
     // TODO this should be parse error, but isn't!
@@ -2207,6 +2207,42 @@ fn shift_right_cast_i8() {
     );
 }
 
+#[test]
+#[cfg(any(feature = "gen-llvm", feature = "gen-wasm", feature = "gen-dev"))]
+fn u128_add_into_record() {
+    assert_evals_to!(
+        indoc!(
+            r#"
+            a = 18_446_744_073_709_551_615u128
+            b = 1u128
+
+            rec = { total: a + b, tag: 7u8 }
+
+            rec.total
+            "#
+        ),
+        18_446_744_073_709_551_616u128,
+        u128
+    );
+}
+
+#[test]
+#[cfg(any(feature = "gen-llvm", feature = "gen-wasm", feature = "gen-dev"))]
+fn i128_record_field() {
+    assert_evals_to!(
+        indoc!(
+            r#"
+            getBig : { flag : U8, big : I128 } -> I128
+            getBig = \rec -> rec.big
+
+            getBig { flag: 1u8, big: -18_446_744_073_709_551_617i128 }
+            "#
+        ),
+        -18_446_744_073_709_551_617i128,
+        i128
+    );
+}
+
 #[test]
 #[cfg(any(feature = "gen-llvm", feature = "gen-wasm", feature = "gen-dev"))]
 fn min_i128() {
@@ -87,6 +87,22 @@ fn f64_record() {
     );
 }
 
+#[test]
+#[cfg(any(feature = "gen-llvm", feature = "gen-dev", feature = "gen-wasm"))]
+fn f32_record_unaligned_field() {
+    assert_evals_to!(
+        indoc!(
+            r#"
+                   rec = { y: 2.25f32, x: 1.5f32 }
+
+                   rec.y + rec.x
+                "#
+        ),
+        3.75,
+        f32
+    );
+}
+
 #[test]
 #[cfg(any(feature = "gen-llvm", feature = "gen-wasm", feature = "gen-dev"))]
 fn pass_bool_record() {
@@ -1150,3 +1166,47 @@ fn pass_record_of_u8s() {
         bool
     )
 }
+
+#[test]
+#[cfg(any(feature = "gen-llvm", feature = "gen-dev", feature = "gen-wasm"))]
+fn join_point_record_parameter() {
+    assert_evals_to!(
+        indoc!(
+            r#"
+            app "test" provides [main] to "./platform"
+
+            loop : { count : I64, total : I64 } -> I64
+            loop = \state ->
+                if state.count == 0 then
+                    state.total
+                else
+                    loop { count: state.count - 1, total: state.total + state.count }
+
+            main =
+                loop { count: 4, total: 0 }
+            "#
+        ),
+        10,
+        i64
+    );
+}
+
+#[test]
+#[cfg(any(feature = "gen-llvm", feature = "gen-dev", feature = "gen-wasm"))]
+fn return_two_eightbyte_record() {
+    assert_evals_to!(
+        indoc!(
+            r#"
+            app "test" provides [main] to "./platform"
+
+            makePoint : I64, I64 -> { x : I64, y : I64 }
+            makePoint = \a, b -> { x: a * 2, y: b + 1 }
+
+            main =
+                makePoint 21 41
+            "#
+        ),
+        (42, 42),
+        (i64, i64)
+    );
+}
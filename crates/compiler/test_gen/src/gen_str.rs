@@ -2204,3 +2204,31 @@ fn str_contains_self() {
         bool
     );
 }
+
+#[test]
+#[cfg(any(feature = "gen-llvm", feature = "gen-wasm", feature = "gen-dev"))]
+fn str_in_record() {
+    assert_evals_to!(
+        indoc!(
+            r#"
+            rec = { name: "hi", age: 3u8 }
+
+            rec.name
+            "#
+        ),
+        RocStr::from("hi"),
+        RocStr
+    );
+
+    assert_evals_to!(
+        indoc!(
+            r#"
+            rec = { name: "a string that is too long to be a small string", age: 3u8 }
+
+            rec.name
+            "#
+        ),
+        RocStr::from("a string that is too long to be a small string"),
+        RocStr
+    );
+}
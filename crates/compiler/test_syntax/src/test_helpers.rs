@@ -9,6 +9,7 @@ use roc_parse::{
 };
 use roc_test_utils::assert_multiline_str_eq;
 
+use roc_fmt::expr::MultilineCache;
 use roc_fmt::spaces::RemoveSpaces;
 use roc_fmt::Buf;
 
@@ -65,6 +66,20 @@ pub enum Output<'a> {
 
 impl<'a> Output<'a> {
     fn format(&self) -> InputOwned {
+        let output = self.format_help();
+
+        // Memoizing `is_multiline` must never change the formatted output
+        let cached_output = {
+            let _multiline_cache = MultilineCache::enable();
+            self.format_help()
+        };
+
+        assert_multiline_str_eq!(output.as_ref().as_str(), cached_output.as_ref().as_str());
+
+        output
+    }
+
+    fn format_help(&self) -> InputOwned {
         let arena = Bump::new();
         let mut buf = Buf::new_in(&arena);
         match self {
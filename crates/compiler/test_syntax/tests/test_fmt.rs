@@ -1043,6 +1043,34 @@ mod test_fmt {
         ));
     }
 
+    #[test]
+    fn interpolation_containing_string_literals() {
+        expr_formats_same(indoc!(
+            r#"
+            "a \(Str.concat "b" "c") d"
+            "#
+        ));
+
+        expr_formats_same(indoc!(
+            r#"
+            "a \(Str.concat "\"b\"" "c\n") d"
+            "#
+        ));
+
+        expr_formats_to(
+            indoc!(
+                r#"
+                "a \(Str.concat  "b"   "c") d"
+                "#
+            ),
+            indoc!(
+                r#"
+                "a \(Str.concat "b" "c") d"
+                "#
+            ),
+        );
+    }
+
     #[test]
     fn empty_block_string() {
         expr_formats_same(indoc!(
@@ -1188,6 +1216,53 @@ mod test_fmt {
         ));
     }
 
+    #[test]
+    fn hex_int_digits_are_uppercase() {
+        expr_formats_same("0xFF");
+        expr_formats_to("0xff", "0xFF");
+        expr_formats_to("0xfF", "0xFF");
+        expr_formats_to("-0xab", "-0xAB");
+    }
+
+    #[test]
+    fn hex_int_keeps_underscores() {
+        expr_formats_same("0xFF_FF");
+        expr_formats_to("0xff_ff", "0xFF_FF");
+        expr_formats_to("0xffff_ffff", "0xFFFF_FFFF");
+    }
+
+    #[test]
+    fn binary_and_octal_int_keep_underscores() {
+        expr_formats_same("0b1010_1010");
+        expr_formats_same("0o17_77");
+    }
+
+    #[test]
+    fn uppercase_base_prefix_is_left_alone() {
+        // The parser only accepts a lowercase `0x` prefix, so this is not a hex literal.
+        expr_formats_same("0XFF");
+    }
+
+    #[test]
+    fn hex_int_pattern_digits_are_uppercase() {
+        expr_formats_to(
+            indoc!(
+                r#"
+                when x is
+                    0xff_ff -> 1
+                    _ -> 0
+                "#
+            ),
+            indoc!(
+                r#"
+                when x is
+                    0xFF_FF -> 1
+                    _ -> 0
+                "#
+            ),
+        );
+    }
+
     #[test]
     fn multi_arg_closure() {
         expr_formats_same(indoc!(
@@ -2983,6 +3058,85 @@ mod test_fmt {
         );
     }
 
+    #[test]
+    fn multi_line_par() {
+        expr_formats_same(indoc!(
+            r#"
+            par (
+                a,
+                b,
+                c,
+            )
+            "#
+        ));
+
+        expr_formats_to(
+            indoc!(
+                r#"
+                par ( a
+                , b
+                , c
+                )
+                "#
+            ),
+            indoc!(
+                r#"
+                par (
+                    a,
+                    b,
+                    c,
+                )
+                "#
+            ),
+        );
+    }
+
+    #[test]
+    fn multi_line_par_nested() {
+        expr_formats_same(indoc!(
+            r#"
+            x =
+                y = par (
+                    a,
+                    b,
+                    c,
+                )
+
+                y
+
+            x
+            "#
+        ));
+
+        expr_formats_to(
+            indoc!(
+                r#"
+                x =
+                    y = par (a,
+                        b, c)
+
+                    y
+
+                x
+                "#
+            ),
+            indoc!(
+                r#"
+                x =
+                    y = par (
+                        a,
+                        b,
+                        c,
+                    )
+
+                    y
+
+                x
+                "#
+            ),
+        );
+    }
+
     // RECORD LITERALS
 
     #[test]
@@ -3229,6 +3383,48 @@ mod test_fmt {
         ));
     }
 
+    #[test]
+    fn single_line_if_normalizes_spaces() {
+        expr_formats_to(
+            indoc!(
+                r#"
+                if foo then  a else b
+                "#
+            ),
+            indoc!(
+                r#"
+                if foo then a else b
+                "#
+            ),
+        );
+
+        expr_formats_to(
+            indoc!(
+                r#"
+                if  foo   then    a   else  b
+                "#
+            ),
+            indoc!(
+                r#"
+                if foo then a else b
+                "#
+            ),
+        );
+
+        expr_formats_to(
+            indoc!(
+                r#"
+                if foo  then  a  else if bar  then  b  else  c
+                "#
+            ),
+            indoc!(
+                r#"
+                if foo then a else if bar then b else c
+                "#
+            ),
+        );
+    }
+
     #[test]
     fn multi_line_if_condition() {
         expr_formats_same(indoc!(
@@ -3613,6 +3809,91 @@ mod test_fmt {
         ));
     }
 
+    /// Expressions whose formatting goes through the more fragile paths in expr.rs
+    /// (outdenting a trailing collection argument, newlines inside parens,
+    /// negating a block string, comments between `when` branches).
+    const IDEMPOTENCY_CORPUS: &[&str] = &[
+        indoc!(
+            r#"
+            result = func arg
+                [
+                    1,
+                    2,
+                    3,
+                ]
+
+            result
+            "#
+        ),
+        indoc!(
+            r#"
+            toList = \_ ->
+                [
+                    1,
+                    2,
+                    3,
+                ]
+
+            toList
+            "#
+        ),
+        indoc!(
+            r#"
+            x = foo (baz {
+              bar: blah
+            })
+            x
+            "#
+        ),
+        indoc!(
+            r#"
+            B:{}
+
+            (
+            a)
+            "#
+        ),
+        indoc!(
+            r#"
+            (i#abc
+            )
+            "#
+        ),
+        r#"-"""""""#,
+        r#"-""""<""""#,
+        indoc!(
+            r#"
+            when a is
+                _ ->
+                    when b is
+                        # look at cases
+                        1 -> # when 1
+                            1
+
+
+                        # important
+                        # fall through
+                        _ ->
+                            # case 2
+                            # more comment
+                            2
+            "#
+        ),
+    ];
+
+    #[test]
+    fn expr_formatting_is_idempotent() {
+        for input in IDEMPOTENCY_CORPUS {
+            Input::Expr(input.trim()).check_invariants(
+                |formatted| {
+                    // Formatting the formatted output again must give back the exact same bytes
+                    formatted.check_invariants(check_formatting(formatted.as_str()), false)
+                },
+                true,
+            );
+        }
+    }
+
     #[test]
     fn def_when() {
         expr_formats_same(indoc!(
@@ -4026,6 +4307,76 @@ mod test_fmt {
         );
     }
 
+    #[test]
+    fn top_level_defs_without_blank_line() {
+        module_formats_same(indoc!(
+            r#"
+            interface Foo exposes [] imports []
+
+            a = 1
+            b = 2
+            "#
+        ));
+    }
+
+    #[test]
+    fn top_level_defs_keep_one_blank_line() {
+        module_formats_same(indoc!(
+            r#"
+            interface Foo exposes [] imports []
+
+            a = 1
+            b = 2
+
+            c = 3
+            "#
+        ));
+
+        module_formats_same(indoc!(
+            r#"
+            interface Foo exposes [] imports []
+
+            a = 1
+
+            # Second group
+            b = 2
+            "#
+        ));
+    }
+
+    #[test]
+    fn top_level_defs_collapse_blank_lines_to_one() {
+        module_formats_to(
+            indoc!(
+                r#"
+                interface Foo exposes [] imports []
+
+                a = 1
+                b = 2
+
+
+                c = 3
+
+
+
+                d = 4
+                "#
+            ),
+            indoc!(
+                r#"
+                interface Foo exposes [] imports []
+
+                a = 1
+                b = 2
+
+                c = 3
+
+                d = 4
+                "#
+            ),
+        );
+    }
+
     #[test]
     fn def_returning_closure() {
         expr_formats_same(indoc!(
@@ -6003,6 +6354,37 @@ mod test_fmt {
         );
     }
 
+    #[test]
+    fn format_crash_with_block_string() {
+        expr_formats_same(indoc!(
+            r#"
+            crash
+                """
+                Something went
+                very wrong
+                """
+            "#
+        ));
+
+        expr_formats_to(
+            indoc!(
+                r#"
+                crash """Something went
+                very wrong"""
+                "#
+            ),
+            indoc!(
+                r#"
+                crash
+                    """
+                    Something went
+                    very wrong
+                    """
+                "#
+            ),
+        );
+    }
+
     #[test]
     fn issue_6197() {
         expr_formats_to(
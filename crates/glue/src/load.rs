@@ -86,6 +86,8 @@ pub fn generate(
                     linking_strategy,
                     true,
                     None,
+                    None,
+                    false,
                     RocCacheDir::Persistent(cache::roc_cache_dir().as_path()),
                     load_config,
                     Some(dylib_dir.path()),
@@ -1,16 +1,17 @@
 use bincode::{deserialize_from, serialize_into};
-use iced_x86::{Decoder, DecoderOptions, Instruction, OpCodeOperandKind, OpKind};
+use iced_x86::{Code, Decoder, DecoderOptions, Instruction, OpCodeOperandKind, OpKind};
 use memmap2::MmapMut;
 use object::{elf, endian};
 use object::{
     CompressedFileRange, CompressionFormat, LittleEndian as LE, Object, ObjectSection,
-    ObjectSymbol, RelocationKind, RelocationTarget, Section, SectionIndex, SectionKind, Symbol,
-    SymbolIndex, SymbolSection,
+    ObjectSymbol, RelocationEncoding, RelocationKind, RelocationTarget, Section, SectionIndex,
+    SectionKind, Symbol, SymbolIndex, SymbolSection,
 };
 use roc_collections::all::MutMap;
 use roc_error_macros::{internal_error, user_error};
 use serde::{Deserialize, Serialize};
 use std::{
+    borrow::Cow,
     ffi::{c_char, CStr},
     io::{BufReader, BufWriter},
     mem,
@@ -19,14 +20,104 @@ use std::{
 };
 
 use crate::{
-    align_by_constraint, align_to_offset_by_constraint, load_struct_inplace,
-    load_struct_inplace_mut, load_structs_inplace_mut, open_mmap, open_mmap_mut,
+    align_by_constraint, align_to_offset_by_constraint, check_size_budget, load_struct_inplace,
+    load_struct_inplace_mut, load_structs_inplace_mut, open_mmap, open_mmap_mut, LinkError,
 };
 
+mod aarch64;
+
 const MIN_SECTION_ALIGNMENT: usize = 0x40;
 
-// TODO: Analyze if this offset is always correct.
-const PLT_ADDRESS_OFFSET: u64 = 0x10;
+/// A stub in one of the PLT sections, which jumps to the address stored in `got_slot`.
+#[derive(Debug, PartialEq, Eq)]
+struct PltStub {
+    address: u64,
+    size: u64,
+    got_slot: u64,
+}
+
+/// Finds the stubs in a PLT section by decoding it. A stub extends up to the next stub, so its
+/// size is the actual stride of the section rather than an assumed one.
+fn plt_stubs(arch: SurgeryArch, data: &[u8], address: u64) -> Vec<PltStub> {
+    let mut stubs = match arch {
+        SurgeryArch::X86_64 => plt_stubs_x86_64(data, address),
+        SurgeryArch::AArch64 => plt_stubs_aarch64(data, address),
+    };
+
+    let section_end = address + data.len() as u64;
+    let ends: Vec<u64> = stubs
+        .iter()
+        .skip(1)
+        .map(|stub| stub.address)
+        .chain([section_end])
+        .collect();
+    for (stub, end) in stubs.iter_mut().zip(ends) {
+        stub.size = end - stub.address;
+    }
+
+    stubs
+}
+
+/// An x86_64 stub is an indirect jump through a GOT slot, optionally preceded by an `endbr64`.
+fn plt_stubs_x86_64(data: &[u8], address: u64) -> Vec<PltStub> {
+    let mut decoder = Decoder::with_ip(64, data, address, DecoderOptions::NONE);
+    let mut inst = Instruction::default();
+    let mut stubs: Vec<PltStub> = vec![];
+    let mut endbr_address = None;
+
+    while decoder.can_decode() {
+        decoder.decode_out(&mut inst);
+
+        if inst.code() == Code::Endbr64 {
+            endbr_address = Some(inst.ip());
+            continue;
+        }
+
+        if inst.is_jmp_near_indirect() && inst.is_ip_rel_memory_operand() {
+            let stub_address = endbr_address.unwrap_or_else(|| inst.ip());
+            stubs.push(PltStub {
+                address: stub_address,
+                size: 0,
+                got_slot: inst.ip_rel_memory_address(),
+            });
+        }
+        endbr_address = None;
+    }
+
+    stubs
+}
+
+/// An AArch64 stub loads the address from its GOT slot with `adrp x16` and `ldr x17, [x16]`,
+/// and then branches to it with `br x17`. It may start with a `bti c`.
+fn plt_stubs_aarch64(data: &[u8], address: u64) -> Vec<PltStub> {
+    let words: Vec<u32> = data
+        .chunks_exact(4)
+        .map(|word| u32::from_le_bytes(word.try_into().unwrap()))
+        .collect();
+    let mut stubs = vec![];
+
+    for (i, pair) in words.windows(2).enumerate() {
+        let adrp_address = address + 4 * i as u64;
+        let page = match aarch64::adrp_page(pair[0], adrp_address) {
+            Some((16, page)) => page,
+            _ => continue,
+        };
+        if let Some((17, 16, offset)) = aarch64::ldr64_offset(pair[1]) {
+            let stub_address = if i > 0 && words[i - 1] == aarch64::BTI_C {
+                adrp_address - 4
+            } else {
+                adrp_address
+            };
+            stubs.push(PltStub {
+                address: stub_address,
+                size: 0,
+                got_slot: page.wrapping_add(offset),
+            });
+        }
+    }
+
+    stubs
+}
 
 struct ElfDynamicDeps {
     got_app_syms: Vec<(String, usize)>,
@@ -42,6 +133,48 @@ enum VirtualOffset {
     Relative(u64),
 }
 
+/// The instruction set of the host. It decides how surgery rewrites calls and PLT stubs.
+#[derive(Default, Serialize, Deserialize, PartialEq, Eq, Debug, Clone, Copy)]
+enum SurgeryArch {
+    #[default]
+    X86_64,
+    AArch64,
+}
+
+impl SurgeryArch {
+    /// The dynamic relocation that fills a GOT slot with the address of a symbol.
+    fn glob_dat(self) -> u32 {
+        match self {
+            SurgeryArch::X86_64 => elf::R_X86_64_GLOB_DAT,
+            SurgeryArch::AArch64 => elf::R_AARCH64_GLOB_DAT,
+        }
+    }
+
+    /// The dynamic relocation that fills the GOT slot of a PLT stub.
+    fn jump_slot(self) -> u32 {
+        match self {
+            SurgeryArch::X86_64 => elf::R_X86_64_JUMP_SLOT,
+            SurgeryArch::AArch64 => elf::R_AARCH64_JUMP_SLOT,
+        }
+    }
+
+    /// The dynamic relocation that adds the load address to its addend.
+    fn relative(self) -> u32 {
+        match self {
+            SurgeryArch::X86_64 => elf::R_X86_64_RELATIVE,
+            SurgeryArch::AArch64 => elf::R_AARCH64_RELATIVE,
+        }
+    }
+
+    /// The dynamic relocation that does nothing.
+    fn none(self) -> u32 {
+        match self {
+            SurgeryArch::X86_64 => elf::R_X86_64_NONE,
+            SurgeryArch::AArch64 => elf::R_AARCH64_NONE,
+        }
+    }
+}
+
 #[derive(Serialize, Deserialize, PartialEq, Eq, Debug)]
 struct SurgeryEntry {
     file_offset: u64,
@@ -55,10 +188,11 @@ struct SurgeryEntry {
 // For example, I think preprocessing can deal with all shifting and remove the need for added_byte_count.
 // TODO: we probably should be storing numbers in an endian neutral way.
 #[derive(Default, Serialize, Deserialize, PartialEq, Eq, Debug)]
-struct Metadata {
+pub(crate) struct Metadata {
+    arch: SurgeryArch,
     app_functions: Vec<String>,
-    // offset followed by address.
-    plt_addresses: MutMap<String, (u64, u64)>,
+    // offset, address and size of the stub.
+    plt_addresses: MutMap<String, (u64, u64, u64)>,
     surgeries: MutMap<String, Vec<SurgeryEntry>>,
     dynamic_symbol_indices: MutMap<String, u64>,
     static_symbol_indices: MutMap<String, u64>,
@@ -71,15 +205,25 @@ struct Metadata {
     dynamic_symbol_table_section_offset: u64,
     symbol_table_section_offset: u64,
     symbol_table_size: u64,
+    // The ELF e_type of the host, ET_DYN for a position-independent executable.
+    e_type: u16,
     _macho_cmd_loc: u64,
 }
 
 impl Metadata {
+    /// Written before the metadata itself, so that we can tell a metadata file apart from
+    /// anything else before trusting its contents.
+    const MAGIC: [u8; 4] = *b"RCMD";
+    /// Bump this whenever the layout of `Metadata` (or anything in it) changes.
+    const FORMAT_VERSION: u32 = 1;
+
     fn write_to_file(&self, metadata_filename: &Path) {
         let metadata_file =
             std::fs::File::create(metadata_filename).unwrap_or_else(|e| internal_error!("{}", e));
+        let mut writer = BufWriter::new(metadata_file);
 
-        serialize_into(BufWriter::new(metadata_file), self)
+        serialize_into(&mut writer, &(Self::MAGIC, Self::FORMAT_VERSION))
+            .and_then(|()| serialize_into(&mut writer, self))
             .unwrap_or_else(|err| internal_error!("Failed to serialize metadata: {err}"));
     }
 
@@ -94,7 +238,27 @@ impl Metadata {
             )
         });
 
-        match deserialize_from(BufReader::new(input)) {
+        let mut reader = BufReader::new(input);
+
+        match deserialize_from::<_, ([u8; 4], u32)>(&mut reader) {
+            Ok((magic, version)) if magic == Self::MAGIC && version == Self::FORMAT_VERSION => {}
+            Ok((magic, version)) if magic == Self::MAGIC => {
+                internal_error!(
+                    "The metadata in {} was produced by an incompatible linker version (format {} instead of {}). Please preprocess the host again.",
+                    metadata_filename.display(),
+                    version,
+                    Self::FORMAT_VERSION
+                );
+            }
+            _ => {
+                internal_error!(
+                    "{} is not a surgical linker metadata file. Please preprocess the host again.",
+                    metadata_filename.display()
+                );
+            }
+        }
+
+        match deserialize_from(reader) {
             Ok(data) => data,
             Err(err) => {
                 internal_error!("Failed to deserialize metadata: {}", err);
@@ -103,6 +267,20 @@ impl Metadata {
     }
 }
 
+/// Surgical linking shifts and appends segments, which only works if the host can be loaded
+/// at any address. A non-PIE executable would still link, but load at the wrong address.
+fn non_pie_warning(e_type: u16) -> Option<String> {
+    match e_type {
+        elf::ET_DYN => None,
+        elf::ET_EXEC => Some(String::from(
+            "The host executable is not position-independent (ET_EXEC). The surgical linker expects a PIE host, so the output may not run. Build the host with `-fPIE` or use `--linker=legacy`.",
+        )),
+        other => Some(format!(
+            "The host has an unexpected ELF type ({other}), the surgical linker expects a position-independent executable (ET_DYN)."
+        )),
+    }
+}
+
 fn report_timing(label: &str, duration: Duration) {
     println!("\t{:9.3} ms   {}", duration.as_secs_f64() * 1000.0, label,);
 }
@@ -123,6 +301,16 @@ fn is_roc_undefined(sym: &object::Symbol) -> bool {
     sym.is_undefined() && is_roc_symbol(sym)
 }
 
+/// An undefined symbol is an app function if it has the `roc_` prefix, or if the platform
+/// explicitly lists it, e.g. because it wraps the Roc entry points under other export names.
+fn is_app_function(sym: &object::Symbol, app_function_names: &[String]) -> bool {
+    is_roc_undefined(sym)
+        || (sym.is_undefined()
+            && sym
+                .name()
+                .map_or(false, |name| app_function_names.iter().any(|n| n == name)))
+}
+
 fn collect_roc_definitions<'a>(object: &object::File<'a, &'a [u8]>) -> MutMap<String, u64> {
     let mut vaddresses = MutMap::default();
 
@@ -164,7 +352,157 @@ fn collect_roc_definitions<'a>(object: &object::File<'a, &'a [u8]>) -> MutMap<St
 struct Surgeries<'a> {
     surgeries: MutMap<String, Vec<SurgeryEntry>>,
     app_func_addresses: MutMap<u64, &'a str>,
-    indirect_warning_given: bool,
+}
+
+/// A text section copied out of the object, so that it can be scanned on another thread.
+struct TextSection<'a> {
+    description: String,
+    data: Cow<'a, [u8]>,
+    address: u64,
+    file_offset: u64,
+    compressed: bool,
+}
+
+impl<'a> TextSection<'a> {
+    fn new(sec: &Section<'a, '_>) -> Self {
+        let (file_offset, compressed) = match sec.compressed_file_range() {
+            Ok(CompressedFileRange {
+                format: CompressionFormat::None,
+                offset,
+                ..
+            }) => (offset, false),
+            Ok(range) => (range.offset, true),
+            Err(err) => {
+                internal_error!(
+                    "Issues dealing with section compression for {:+x?}: {}",
+                    sec,
+                    err
+                );
+            }
+        };
+
+        let data = match sec.uncompressed_data() {
+            Ok(data) => data,
+            Err(err) => {
+                internal_error!("Failed to load text section, {:+x?}: {}", sec, err);
+            }
+        };
+
+        Self {
+            description: format!("{sec:+x?}"),
+            data,
+            address: sec.address(),
+            file_offset,
+            compressed,
+        }
+    }
+
+    /// The bytes of the section from offset `start` up to `end`, as a section of their own.
+    fn slice(&self, start: usize, end: usize) -> TextSection<'_> {
+        TextSection {
+            description: self.description.clone(),
+            data: Cow::Borrowed(&self.data[start..end]),
+            address: self.address + start as u64,
+            file_offset: self.file_offset + start as u64,
+            compressed: self.compressed,
+        }
+    }
+}
+
+/// The addresses where a text section can be split, so that the parts can be scanned on their
+/// own. A split must be at the start of an instruction, and must not cut a relocation in half.
+struct TextSplitPoints {
+    arch: SurgeryArch,
+    /// The sorted addresses of the functions. On x86_64 instructions have different lengths,
+    /// so these are the only instruction starts that are known without decoding.
+    functions: Vec<u64>,
+    /// The sorted address ranges that are patched by dynamic relocations.
+    relocations: Vec<(u64, u64)>,
+}
+
+impl TextSplitPoints {
+    fn new(arch: SurgeryArch, object: &object::File) -> Self {
+        let mut functions: Vec<u64> = object
+            .symbols()
+            .chain(object.dynamic_symbols())
+            .filter(|sym| sym.kind() == object::SymbolKind::Text && sym.is_definition())
+            .map(|sym| sym.address())
+            .collect();
+        functions.sort_unstable();
+        functions.dedup();
+
+        let mut relocations: Vec<(u64, u64)> = object
+            .dynamic_relocations()
+            .into_iter()
+            .flatten()
+            .map(|(address, rel)| (address, address + (rel.size() as u64 / 8).max(1)))
+            .collect();
+        relocations.sort_unstable();
+
+        Self {
+            arch,
+            functions,
+            relocations,
+        }
+    }
+
+    /// The first split point at or after `start` and before `end`.
+    fn first_in(&self, mut start: u64, end: u64) -> Option<u64> {
+        loop {
+            let candidate = match self.arch {
+                SurgeryArch::X86_64 => {
+                    let index = self.functions.partition_point(|address| *address < start);
+                    *self.functions.get(index)?
+                }
+                // Every instruction is one aligned word.
+                SurgeryArch::AArch64 => (start + 3) & !3,
+            };
+            if candidate >= end {
+                return None;
+            }
+
+            let index = self
+                .relocations
+                .partition_point(|(_, reloc_end)| *reloc_end <= candidate);
+            match self.relocations.get(index) {
+                Some((reloc_start, reloc_end)) if *reloc_start < candidate => start = *reloc_end,
+                _ => return Some(candidate),
+            }
+        }
+    }
+}
+
+/// Splits the sections into chunks of about the same size, so that about `chunk_count` of
+/// them cover all of the code. A section without split points stays in one chunk.
+fn split_text_sections<'b>(
+    sections: &'b [TextSection],
+    split_points: &TextSplitPoints,
+    chunk_count: usize,
+) -> Vec<TextSection<'b>> {
+    let total_len: usize = sections.iter().map(|sec| sec.data.len()).sum();
+    let chunk_len = ((total_len + chunk_count - 1) / chunk_count).max(1);
+    let mut chunks = vec![];
+
+    for sec in sections {
+        let end = sec.address + sec.data.len() as u64;
+        let mut start = 0;
+
+        while sec.data.len() - start > chunk_len {
+            let from = sec.address + (start + chunk_len) as u64;
+            match split_points.first_in(from, end) {
+                Some(split) => {
+                    let split = (split - sec.address) as usize;
+                    chunks.push(sec.slice(start, split));
+                    start = split;
+                }
+                None => break,
+            }
+        }
+
+        chunks.push(sec.slice(start, sec.data.len()));
+    }
+
+    chunks
 }
 
 impl<'a> Surgeries<'a> {
@@ -181,12 +519,12 @@ impl<'a> Surgeries<'a> {
         Self {
             surgeries,
             app_func_addresses,
-            indirect_warning_given: false,
         }
     }
 
     fn append_text_sections(
         &mut self,
+        arch: SurgeryArch,
         object_bytes: &[u8],
         object: &object::File<'a, &'a [u8]>,
         verbose: bool,
@@ -211,136 +549,219 @@ impl<'a> Surgeries<'a> {
             println!("Analyzing instuctions for branches");
         }
 
-        for text_section in text_sections {
-            self.append_text_section(object_bytes, &text_section, verbose)
+        // Decoding dominates preprocessing for large hosts. Nearly all of the code is usually
+        // in one `.text` section, so the sections are split into about one chunk per available
+        // core, and each chunk is scanned on its own thread.
+        // The app function addresses are only read while scanning.
+        let text_sections: Vec<TextSection> = text_sections.iter().map(TextSection::new).collect();
+        let thread_count = std::thread::available_parallelism().map_or(1, |count| count.get());
+        let split_points = TextSplitPoints::new(arch, object);
+        let chunks = split_text_sections(&text_sections, &split_points, thread_count);
+        let app_func_addresses = &self.app_func_addresses;
+        let results: Vec<_> = std::thread::scope(|scope| {
+            let handles: Vec<_> = chunks
+                .iter()
+                .map(|chunk| {
+                    scope.spawn(move || scan_text_section(arch, app_func_addresses, chunk))
+                })
+                .collect();
+
+            handles
+                .into_iter()
+                .map(|handle| {
+                    handle
+                        .join()
+                        .unwrap_or_else(|err| std::panic::resume_unwind(err))
+                })
+                .collect()
+        });
+
+        let mut found_indirect = false;
+        let mut entries = vec![];
+        for (section_entries, section_found_indirect) in results {
+            entries.extend(section_entries);
+            found_indirect |= section_found_indirect;
         }
-    }
 
-    fn append_text_section(&mut self, object_bytes: &[u8], sec: &Section, verbose: bool) {
-        let (file_offset, compressed) = match sec.compressed_file_range() {
-            Ok(CompressedFileRange {
-                format: CompressionFormat::None,
-                offset,
-                ..
-            }) => (offset, false),
-            Ok(range) => (range.offset, true),
-            Err(err) => {
-                internal_error!(
-                    "Issues dealing with section compression for {:+x?}: {}",
-                    sec,
-                    err
+        // Sort so that the surgeries and the verbose output do not depend on thread timing.
+        entries.sort_by_key(|(_, entry)| entry.file_offset);
+
+        if verbose {
+            for (func_name, entry) in entries.iter() {
+                let offset = entry.file_offset as usize;
+                println!(
+                    "Found branch to {}, need to surgically replace {} bytes at file offset {:+x}",
+                    func_name, entry.size, offset
+                );
+                println!(
+                    "\tIts current value is {:+x?}",
+                    &object_bytes[offset..offset + entry.size as usize]
                 );
             }
-        };
 
-        let data = match sec.uncompressed_data() {
-            Ok(data) => data,
-            Err(err) => {
-                internal_error!("Failed to load text section, {:+x?}: {}", sec, err);
+            if found_indirect {
+                println!();
+                println!("Cannot analyze through indirect jmp type instructions");
+                println!(
+                    "Most likely this is not a problem, but it could mean a loss in optimizations"
+                );
+                println!();
             }
-        };
-        let mut decoder = Decoder::with_ip(64, &data, sec.address(), DecoderOptions::NONE);
-        let mut inst = Instruction::default();
-
-        while decoder.can_decode() {
-            decoder.decode_out(&mut inst);
-
-            // Note: This gets really complex fast if we want to support more than basic calls/jumps.
-            // A lot of them have to load addresses into registers/memory so we would have to discover that value.
-            // Would probably require some static code analysis and would be impossible in some cases.
-            // As an alternative we can leave in the calls to the plt, but change the plt to jmp to the static function.
-            // That way any indirect call will just have the overhead of an extra jump.
-            match inst.try_op_kind(0) {
-                // Relative Offsets.
-                Ok(OpKind::NearBranch16 | OpKind::NearBranch32 | OpKind::NearBranch64) => {
-                    let target = inst.near_branch_target();
-                    if let Some(func_name) = self.app_func_addresses.get(&target) {
-                        if compressed {
-                            internal_error!("Surgical linking does not work with compressed text sections: {:+x?}", sec);
-                        }
+        }
 
-                        if verbose {
-                            println!(
-                                "Found branch from {:+x} to {:+x}({})",
-                                inst.ip(),
-                                target,
-                                func_name
-                            );
-                        }
+        for (func_name, entry) in entries {
+            self.surgeries.get_mut(func_name).unwrap().push(entry);
+        }
+    }
+}
 
-                        // TODO: Double check these offsets are always correct.
-                        // We may need to do a custom offset based on opcode instead.
-                        let op_kind = inst.op_code().try_op_kind(0).unwrap();
-                        let op_size: u8 = match op_kind {
-                            OpCodeOperandKind::br16_1 | OpCodeOperandKind::br32_1 => 1,
-                            OpCodeOperandKind::br16_2 => 2,
-                            OpCodeOperandKind::br32_4 | OpCodeOperandKind::br64_4 => 4,
-                            _ => {
-                                internal_error!(
-                                    "Ran into an unknown operand kind when analyzing branches: {:?}",
-                                    op_kind
-                                );
-                            }
-                        };
-                        let offset = inst.next_ip() - op_size as u64 - sec.address() + file_offset;
-                        if verbose {
-                            println!(
-                                "\tNeed to surgically replace {op_size} bytes at file offset {offset:+x}",
+/// Finds the branches to app functions in a text section. Also returns whether any indirect
+/// jumps or calls were found, because we cannot analyze those.
+fn scan_text_section<'a>(
+    arch: SurgeryArch,
+    app_func_addresses: &MutMap<u64, &'a str>,
+    sec: &TextSection,
+) -> (Vec<(&'a str, SurgeryEntry)>, bool) {
+    match arch {
+        SurgeryArch::X86_64 => scan_text_section_x86_64(app_func_addresses, sec),
+        SurgeryArch::AArch64 => scan_text_section_aarch64(app_func_addresses, sec),
+    }
+}
+
+fn scan_text_section_x86_64<'a>(
+    app_func_addresses: &MutMap<u64, &'a str>,
+    sec: &TextSection,
+) -> (Vec<(&'a str, SurgeryEntry)>, bool) {
+    let mut entries = vec![];
+    let mut found_indirect = false;
+
+    let mut decoder = Decoder::with_ip(64, &sec.data, sec.address, DecoderOptions::NONE);
+    let mut inst = Instruction::default();
+
+    while decoder.can_decode() {
+        decoder.decode_out(&mut inst);
+
+        // Note: This gets really complex fast if we want to support more than basic calls/jumps.
+        // A lot of them have to load addresses into registers/memory so we would have to discover that value.
+        // Would probably require some static code analysis and would be impossible in some cases.
+        // As an alternative we can leave in the calls to the plt, but change the plt to jmp to the static function.
+        // That way any indirect call will just have the overhead of an extra jump.
+        match inst.try_op_kind(0) {
+            // Relative Offsets.
+            Ok(OpKind::NearBranch16 | OpKind::NearBranch32 | OpKind::NearBranch64) => {
+                let target = inst.near_branch_target();
+                if let Some(func_name) = app_func_addresses.get(&target) {
+                    if sec.compressed {
+                        internal_error!(
+                            "Surgical linking does not work with compressed text sections: {}",
+                            sec.description
+                        );
+                    }
+
+                    // TODO: Double check these offsets are always correct.
+                    // We may need to do a custom offset based on opcode instead.
+                    let op_kind = inst.op_code().try_op_kind(0).unwrap();
+                    let op_size: u8 = match op_kind {
+                        OpCodeOperandKind::br16_1 | OpCodeOperandKind::br32_1 => 1,
+                        OpCodeOperandKind::br16_2 => 2,
+                        OpCodeOperandKind::br32_4 | OpCodeOperandKind::br64_4 => 4,
+                        _ => {
+                            internal_error!(
+                                "Ran into an unknown operand kind when analyzing branches: {:?}",
+                                op_kind
                             );
-                            println!(
-                                "\tIts current value is {:+x?}",
-                                &object_bytes[offset as usize..(offset + op_size as u64) as usize]
-                            )
                         }
-                        self.surgeries
-                            .get_mut(*func_name)
-                            .unwrap()
-                            .push(SurgeryEntry {
-                                file_offset: offset,
-                                virtual_offset: VirtualOffset::Relative(inst.next_ip()),
-                                size: op_size,
-                            });
-                    }
+                    };
+                    let offset = inst.next_ip() - op_size as u64 - sec.address + sec.file_offset;
+                    entries.push((
+                        *func_name,
+                        SurgeryEntry {
+                            file_offset: offset,
+                            virtual_offset: VirtualOffset::Relative(inst.next_ip()),
+                            size: op_size,
+                        },
+                    ));
                 }
-                Ok(OpKind::FarBranch16 | OpKind::FarBranch32) => {
-                    internal_error!(
-                        "Found branch type instruction that is not yet support: {:+x?}",
-                        inst
-                    );
+            }
+            Ok(OpKind::FarBranch16 | OpKind::FarBranch32) => {
+                internal_error!(
+                    "Found branch type instruction that is not yet support: {:+x?}",
+                    inst
+                );
+            }
+            Ok(_) => {
+                if inst.is_call_far_indirect()
+                    || inst.is_call_near_indirect()
+                    || inst.is_jmp_far_indirect()
+                    || inst.is_jmp_near_indirect()
+                {
+                    found_indirect = true;
                 }
-                Ok(_) => {
-                    if (inst.is_call_far_indirect()
-                        || inst.is_call_near_indirect()
-                        || inst.is_jmp_far_indirect()
-                        || inst.is_jmp_near_indirect())
-                        && !self.indirect_warning_given
-                        && verbose
-                    {
-                        self.indirect_warning_given = true;
-                        println!();
-                        println!("Cannot analyze through indirect jmp type instructions");
-                        println!("Most likely this is not a problem, but it could mean a loss in optimizations");
-                        println!();
+            }
+            Err(err) => {
+                internal_error!("Failed to decode assembly: {}", err);
+            }
+        }
+    }
+
+    (entries, found_indirect)
+}
+
+/// AArch64 calls and tail calls are `bl` and `b`. Their offset is a field of the instruction
+/// rather than its trailing bytes, so the surgery entry covers the whole instruction and is
+/// relative to its own address.
+fn scan_text_section_aarch64<'a>(
+    app_func_addresses: &MutMap<u64, &'a str>,
+    sec: &TextSection,
+) -> (Vec<(&'a str, SurgeryEntry)>, bool) {
+    let mut entries = vec![];
+    let mut found_indirect = false;
+
+    for (i, inst) in sec.data.chunks_exact(4).enumerate() {
+        let inst = u32::from_le_bytes(inst.try_into().unwrap());
+        let address = sec.address + 4 * i as u64;
+
+        match aarch64::branch_target(inst, address) {
+            Some(target) => {
+                if let Some(func_name) = app_func_addresses.get(&target) {
+                    if sec.compressed {
+                        internal_error!(
+                            "Surgical linking does not work with compressed text sections: {}",
+                            sec.description
+                        );
                     }
-                }
-                Err(err) => {
-                    internal_error!("Failed to decode assembly: {}", err);
+
+                    entries.push((
+                        *func_name,
+                        SurgeryEntry {
+                            file_offset: sec.file_offset + 4 * i as u64,
+                            virtual_offset: VirtualOffset::Relative(address),
+                            size: 4,
+                        },
+                    ));
                 }
             }
+            None => found_indirect |= aarch64::is_indirect_branch(inst),
         }
     }
+
+    (entries, found_indirect)
 }
 
-/// Constructs a `Metadata` from a host executable binary, and writes it to disk
+/// Constructs a `Metadata` from a host executable binary, and writes it to disk unless
+/// `metadata_path` is `None`
+#[allow(clippy::too_many_arguments)]
 pub(crate) fn preprocess_elf(
     endianness: target_lexicon::Endianness,
     host_exe_path: &Path,
-    metadata_path: &Path,
+    metadata_path: Option<&Path>,
     preprocessed_path: &Path,
     shared_lib: &Path,
+    app_function_names: &[String],
+    dry_run: bool,
     verbose: bool,
     time: bool,
-) {
+) -> Result<Metadata, LinkError> {
     let total_start = Instant::now();
     let exec_parsing_start = total_start;
     let exec_data = &*open_mmap(host_exe_path);
@@ -351,11 +772,25 @@ pub(crate) fn preprocess_elf(
         }
     };
 
+    let arch = match exec_obj.architecture() {
+        object::Architecture::X86_64 => SurgeryArch::X86_64,
+        object::Architecture::Aarch64 => SurgeryArch::AArch64,
+        other => return Err(LinkError::UnsupportedArch(other)),
+    };
+
     let mut md = Metadata {
+        arch,
         roc_symbol_vaddresses: collect_roc_definitions(&exec_obj),
+        e_type: load_struct_inplace::<elf::FileHeader64<LE>>(exec_data, 0)
+            .e_type
+            .get(LE),
         ..Default::default()
     };
 
+    if let Some(warning) = non_pie_warning(md.e_type) {
+        eprintln!("Warning: {warning}");
+    }
+
     if verbose {
         println!(
             "Found {} roc symbol definitions:",
@@ -388,72 +823,97 @@ pub(crate) fn preprocess_elf(
     // procedures/functions whose address isn't known in the time of linking, and is left
     // to be resolved by the dynamic linker at run time.
     let symbol_and_plt_processing_start = Instant::now();
-    let plt_section_name = ".plt";
-    let (plt_address, plt_offset) = match exec_obj.section_by_name(plt_section_name) {
-        Some(section) => {
-            let file_offset = match section.compressed_file_range() {
-                Ok(
-                    range @ CompressedFileRange {
-                        format: CompressionFormat::None,
-                        ..
-                    },
-                ) => range.offset,
-                _ => {
-                    internal_error!("Surgical linking does not work with compressed plt section");
-                }
-            };
-            (section.address(), file_offset)
-        }
-        None => {
-            internal_error!("Failed to find PLT section. Probably an malformed executable.");
-        }
-    };
-    if verbose {
-        println!("PLT Address: {plt_address:+x}");
-        println!("PLT File Offset: {plt_offset:+x}");
-    }
 
     let app_syms: Vec<_> = exec_obj
         .dynamic_symbols()
-        .filter(is_roc_undefined)
+        .filter(|sym| is_app_function(sym, app_function_names))
         .collect();
 
-    let mut app_func_addresses: MutMap<u64, &str> = MutMap::default();
+    if app_syms.is_empty() {
+        eprintln!(
+            "Warning: the host does not import any app functions, so surgical linking will not connect it to the app. This usually means the wrong shared library or executable was passed."
+        );
+    }
 
-    let plt_relocs = (match exec_obj.dynamic_relocations() {
-                Some(relocs) => relocs,
-                None => {
-                    internal_error!("Executable does not have any dynamic relocations. No work to do. Probably an invalid input.");
-                }
-            })
-            .filter_map(|(_, reloc)| {
-                if let RelocationKind::Elf(7) = reloc.kind() {
-                    Some(reloc)
-                } else {
-                    None
+    // The GOT slots that the dynamic linker fills in with app functions.
+    let mut app_got_slots: MutMap<u64, &str> = MutMap::default();
+    let dynamic_relocs = match exec_obj.dynamic_relocations() {
+        Some(relocs) => relocs,
+        None => {
+            internal_error!("Executable does not have any dynamic relocations. No work to do. Probably an invalid input.");
+        }
+    };
+    for (got_slot, reloc) in dynamic_relocs {
+        let r_type = match reloc.kind() {
+            RelocationKind::Elf(r_type) => r_type,
+            _ => continue,
+        };
+        if r_type == md.arch.jump_slot() || r_type == md.arch.glob_dat() {
+            for symbol in app_syms.iter() {
+                if reloc.target() == RelocationTarget::Symbol(symbol.index()) {
+                    app_got_slots.insert(got_slot, symbol.name().unwrap());
+                    break;
                 }
-            });
-    for (i, reloc) in plt_relocs.enumerate() {
-        for symbol in app_syms.iter() {
-            if reloc.target() == RelocationTarget::Symbol(symbol.index()) {
-                let func_address = (i as u64 + 1) * PLT_ADDRESS_OFFSET + plt_address;
-                let func_offset = (i as u64 + 1) * PLT_ADDRESS_OFFSET + plt_offset;
-                app_func_addresses.insert(func_address, symbol.name().unwrap());
-                md.plt_addresses.insert(
-                    symbol.name().unwrap().to_string(),
-                    (func_offset, func_address),
+            }
+        }
+    }
+
+    // Calls to app functions go through a stub that jumps via its GOT slot. Depending on the
+    // toolchain the stubs live in .plt (lazy binding), .plt.sec (with -fcf-protection) or
+    // .plt.got (eager binding), so look at all of them and find the stubs by their GOT slot.
+    let mut app_func_addresses: MutMap<u64, &str> = MutMap::default();
+    let mut found_plt = false;
+    for plt_section_name in [".plt", ".plt.sec", ".plt.got"] {
+        let section = match exec_obj.section_by_name(plt_section_name) {
+            Some(section) => section,
+            None => continue,
+        };
+        found_plt = true;
+
+        let plt_offset = match section.compressed_file_range() {
+            Ok(
+                range @ CompressedFileRange {
+                    format: CompressionFormat::None,
+                    ..
+                },
+            ) => range.offset,
+            _ => {
+                internal_error!(
+                    "Surgical linking does not work with compressed {plt_section_name} section"
                 );
-                break;
+            }
+        };
+        let plt_address = section.address();
+        if verbose {
+            println!("{plt_section_name} Address: {plt_address:+x}");
+            println!("{plt_section_name} File Offset: {plt_offset:+x}");
+        }
+
+        let data = section.data().unwrap_or_else(|err| {
+            internal_error!("Failed to load {plt_section_name} section: {err}")
+        });
+        for stub in plt_stubs(md.arch, data, plt_address) {
+            if let Some(name) = app_got_slots.get(&stub.got_slot) {
+                let func_offset = stub.address - plt_address + plt_offset;
+                app_func_addresses.insert(stub.address, *name);
+                md.plt_addresses
+                    .insert(name.to_string(), (func_offset, stub.address, stub.size));
             }
         }
     }
+    if !found_plt {
+        internal_error!("Failed to find PLT section. Probably an malformed executable.");
+    }
 
     for sym in app_syms.iter() {
         let name = sym.name().unwrap().to_string();
         md.app_functions.push(name.clone());
         md.dynamic_symbol_indices.insert(name, sym.index().0 as u64);
     }
-    for sym in exec_obj.symbols().filter(is_roc_undefined) {
+    for sym in exec_obj
+        .symbols()
+        .filter(|sym| is_app_function(sym, app_function_names))
+    {
         let name = sym.name().unwrap().to_string();
         md.static_symbol_indices.insert(name, sym.index().0 as u64);
     }
@@ -474,13 +934,14 @@ pub(crate) fn preprocess_elf(
     let text_disassembly_start = Instant::now();
 
     let mut surgeries = Surgeries::new(&app_syms, app_func_addresses);
-    surgeries.append_text_sections(exec_data, &exec_obj, verbose);
+    surgeries.append_text_sections(md.arch, exec_data, &exec_obj, verbose);
     md.surgeries = surgeries.surgeries;
 
     let text_disassembly_duration = text_disassembly_start.elapsed();
 
     let scanning_dynamic_deps_duration;
     let platform_gen_start;
+    let got_entry_count;
 
     let out_mmap = match endianness {
         target_lexicon::Endianness::Little => {
@@ -498,20 +959,27 @@ pub(crate) fn preprocess_elf(
 
             scanning_dynamic_deps_duration = scanning_dynamic_deps_start.elapsed();
 
+            // Every GOT entry of an app function gets an absolute surgery once the host is generated.
+            got_entry_count = got_app_syms.len();
+
             platform_gen_start = Instant::now();
 
-            // TODO little endian
-            gen_elf_le(
-                exec_data,
-                &mut md,
-                preprocessed_path,
-                &got_app_syms,
-                &got_sections,
-                &app_sym_indices,
-                dynamic_lib_count,
-                shared_lib_index,
-                verbose,
-            )
+            if dry_run {
+                None
+            } else {
+                // TODO little endian
+                Some(gen_elf_le(
+                    exec_data,
+                    &mut md,
+                    preprocessed_path,
+                    &got_app_syms,
+                    &got_sections,
+                    &app_sym_indices,
+                    dynamic_lib_count,
+                    shared_lib_index,
+                    verbose,
+                ))
+            }
         }
         target_lexicon::Endianness::Big => {
             // TODO probably need to make gen_elf a macro to get this
@@ -528,16 +996,32 @@ pub(crate) fn preprocess_elf(
         println!("{md:+x?}");
     }
 
+    // A dry run never lays out the preprocessed host, so its metadata would be incomplete.
+    // Any metadata from an earlier preprocessing is left as it is.
     let saving_metadata_start = Instant::now();
-    md.write_to_file(metadata_path);
+    if !dry_run {
+        if let Some(metadata_path) = metadata_path {
+            md.write_to_file(metadata_path);
+        }
+    }
     let saving_metadata_duration = saving_metadata_start.elapsed();
 
-    let flushing_data_start = Instant::now();
-    out_mmap
-        .flush()
-        .unwrap_or_else(|e| internal_error!("{}", e));
-    // Also drop files to to ensure data is fully written here.
-    drop(out_mmap);
+    if dry_run {
+        let surgery_count: usize = md.surgeries.values().map(Vec::len).sum();
+        println!("Dry run, neither the preprocessed host nor its metadata were written.");
+        println!("App functions: {:?}", md.app_functions);
+        println!("Call sites to patch: {surgery_count}");
+        println!("GOT entries to patch: {got_entry_count}");
+    }
+
+    let flushing_data_start = Instant::now();
+    if let Some(out_mmap) = out_mmap {
+        out_mmap
+            .flush()
+            .unwrap_or_else(|e| internal_error!("{}", e));
+        // Also drop files to to ensure data is fully written here.
+        drop(out_mmap);
+    }
     let flushing_data_duration = flushing_data_start.elapsed();
 
     let total_duration = total_start.elapsed();
@@ -568,6 +1052,8 @@ pub(crate) fn preprocess_elf(
         );
         report_timing("Total", total_duration);
     }
+
+    Ok(md)
 }
 
 #[allow(clippy::too_many_arguments)]
@@ -729,6 +1215,7 @@ fn gen_elf_le(
         }
     }
 
+    let arch = md.arch;
     let dyn_offset = md.dynamic_section_offset + md.added_byte_count;
     for (sec_index, sec_offset, sec_size) in rela_sections {
         let relocations = load_structs_inplace_mut::<elf::Rela64<LE>>(
@@ -742,18 +1229,18 @@ fn gen_elf_le(
                 rel.r_offset.set(LE, r_offset + md.added_byte_count);
                 // Deal with potential adjusts to absolute jumps.
                 // TODO: Verify other relocation types.
-                if rel.r_type(LE, false) == elf::R_X86_64_RELATIVE {
+                if rel.r_type(LE, false) == arch.relative() {
                     let r_addend = rel.r_addend.get(LE);
                     rel.r_addend.set(LE, r_addend + md.added_byte_count as i64);
                 }
             }
             // If the relocation goes to a roc function, we need to surgically link it and change it to relative.
             let r_type = rel.r_type(LE, false);
-            if r_type == elf::R_X86_64_GLOB_DAT {
+            if r_type == arch.glob_dat() {
                 let r_sym = rel.r_sym(LE, false);
                 for (name, index) in got_app_syms.iter() {
                     if *index as u32 == r_sym {
-                        rel.set_r_info(LE, false, 0, elf::R_X86_64_RELATIVE);
+                        rel.set_r_info(LE, false, 0, arch.relative());
                         let addend_addr = sec_offset as usize
                             + i * mem::size_of::<elf::Rela64<LE>>()
                             // This 16 skips the first 2 fields and gets to the addend field.
@@ -778,8 +1265,7 @@ fn gen_elf_le(
             .filter_map(|(i, rel)| {
                 let r_type = rel.r_type(LE, false);
                 let r_sym = rel.r_sym(LE, false);
-                if r_type == elf::R_X86_64_JUMP_SLOT && app_sym_indices.contains(&(r_sym as usize))
-                {
+                if r_type == arch.jump_slot() && app_sym_indices.contains(&(r_sym as usize)) {
                     Some(i)
                 } else {
                     None
@@ -795,7 +1281,7 @@ fn gen_elf_le(
         for i in to_remove.iter() {
             relocations.swap(*i, j);
             let r_sym = relocations[j].r_sym(LE, false);
-            relocations[j].set_r_info(LE, false, r_sym, elf::R_X86_64_NONE);
+            relocations[j].set_r_info(LE, false, r_sym, arch.none());
             j -= 1;
         }
 
@@ -933,14 +1419,12 @@ fn gen_elf_le(
     // TODO: look into shifting all of the debug info and eh_frames.
 
     // Delete shared library from the dynamic table.
-    let out_ptr = out_mmap.as_mut_ptr();
-    unsafe {
-        std::ptr::copy(
-            out_ptr.add(dyn_offset as usize + 16 * (shared_lib_index + 1)),
-            out_ptr.add(dyn_offset as usize + 16 * shared_lib_index),
-            16 * (dynamic_lib_count - shared_lib_index),
-        );
-    }
+    delete_dynamic_entry(
+        &mut out_mmap,
+        dyn_offset as usize,
+        dynamic_lib_count,
+        shared_lib_index,
+    );
 
     // Update main elf header for extra data.
     let file_header = load_struct_inplace_mut::<elf::FileHeader64<LE>>(&mut out_mmap, 0);
@@ -1002,6 +1486,7 @@ fn scan_elf_dynamic_deps(
 
     let mut dyn_lib_index = 0;
     let mut shared_lib_index = None;
+    let mut needed_libs = vec![];
     loop {
         let dyn_tag = u64::from_le_bytes(
             <[u8; 8]>::try_from(
@@ -1021,6 +1506,7 @@ fn scan_elf_dynamic_deps(
             ) as usize;
             let c_buf = dynstr_data[dynstr_off..].as_ptr() as *const c_char;
             let c_str = unsafe { CStr::from_ptr(c_buf) }.to_str().unwrap();
+            needed_libs.push(c_str);
             if Path::new(c_str).file_name() == shared_lib_filename {
                 shared_lib_index = Some(dyn_lib_index);
                 if verbose {
@@ -1034,7 +1520,20 @@ fn scan_elf_dynamic_deps(
     let dynamic_lib_count = dyn_lib_index;
 
     if shared_lib_index.is_none() {
-        panic!("Shared lib not found as a dependency of the executable");
+        let shared_lib_name = shared_lib_filename
+            .and_then(|name| name.to_str())
+            .unwrap_or_default();
+        match needed_libs
+            .iter()
+            .find(|needed| soname_base(needed) == soname_base(shared_lib_name))
+        {
+            Some(candidate) => panic!(
+                "Shared lib {shared_lib_name} not found as a dependency of the executable, but it depends on {candidate}. Did you mean to pass {candidate}?"
+            ),
+            None => panic!(
+                "Shared lib {shared_lib_name} not found as a dependency of the executable, it depends on: {needed_libs:?}"
+            ),
+        }
     }
     let shared_lib_index = shared_lib_index.unwrap();
 
@@ -1103,7 +1602,7 @@ fn scan_elf_dynamic_deps(
         }
     })
     .filter_map(|(_, reloc)| {
-        if let RelocationKind::Elf(elf::R_X86_64_GLOB_DAT) = reloc.kind() {
+        if reloc.kind() == RelocationKind::Elf(md.arch.glob_dat()) {
             for symbol in app_syms.iter() {
                 if reloc.target() == RelocationTarget::Symbol(symbol.index()) {
                     return Some((symbol.name().unwrap().to_string(), symbol.index().0));
@@ -1122,7 +1621,7 @@ fn scan_elf_dynamic_deps(
         }
     })
     .filter_map(|(_, reloc)| {
-        if let RelocationKind::Elf(elf::R_X86_64_JUMP_SLOT) = reloc.kind() {
+        if reloc.kind() == RelocationKind::Elf(md.arch.jump_slot()) {
             for symbol in app_syms.iter() {
                 if reloc.target() == RelocationTarget::Symbol(symbol.index()) {
                     return Some(symbol.index().0);
@@ -1142,13 +1641,254 @@ fn scan_elf_dynamic_deps(
     }
 }
 
+/// Removes entry `index` from the dynamic table at `dyn_offset`, where `entry_count` is the
+/// index of its DT_NULL terminator. The table ends up one entry shorter and still terminated.
+fn delete_dynamic_entry(data: &mut [u8], dyn_offset: usize, entry_count: usize, index: usize) {
+    let entry_size = mem::size_of::<elf::Dyn64<LE>>();
+    debug_assert!(index < entry_count);
+
+    // Shift the entries after `index` down, up to and including the terminator.
+    data.copy_within(
+        dyn_offset + entry_size * (index + 1)..dyn_offset + entry_size * (entry_count + 1),
+        dyn_offset + entry_size * index,
+    );
+
+    // Explicitly terminate the shortened table and clear the slot that was vacated.
+    for i in [entry_count - 1, entry_count] {
+        let entry = load_struct_inplace_mut::<elf::Dyn64<LE>>(data, dyn_offset + entry_size * i);
+        entry.d_tag.set(LE, elf::DT_NULL as u64);
+        entry.d_val.set(LE, 0);
+    }
+}
+
+/// Points a call site recorded during preprocessing at the app function at `func_virt_offset`.
+fn perform_surgery(
+    data: &mut [u8],
+    md: &Metadata,
+    s: &SurgeryEntry,
+    func_virt_offset: u64,
+    verbose: bool,
+) {
+    let surgery_virt_offset = match s.virtual_offset {
+        VirtualOffset::Relative(vs) => (vs + md.added_byte_count) as i64,
+        VirtualOffset::Absolute => 0,
+    };
+    let file_offset = (s.file_offset + md.added_byte_count) as usize;
+    if let (SurgeryArch::AArch64, VirtualOffset::Relative(_)) = (md.arch, &s.virtual_offset) {
+        if verbose {
+            println!("\tTarget Branch: {func_virt_offset:+x}");
+        }
+        aarch64::write_relocation(
+            data,
+            file_offset,
+            aarch64::Relocation::Branch26,
+            surgery_virt_offset as u64,
+            func_virt_offset,
+        );
+        return;
+    }
+    match s.size {
+        4 => {
+            let target = (func_virt_offset as i64 - surgery_virt_offset) as i32;
+            if verbose {
+                println!("\tTarget Jump: {target:+x}");
+            }
+            data[file_offset..][..4].copy_from_slice(&target.to_le_bytes());
+        }
+        8 => {
+            let target = func_virt_offset as i64 - surgery_virt_offset;
+            if verbose {
+                println!("\tTarget Jump: {target:+x}");
+            }
+            data[file_offset..][..8].copy_from_slice(&target.to_le_bytes());
+        }
+        x => {
+            internal_error!("Surgery size not yet supported: {}", x);
+        }
+    }
+}
+
+/// Replaces the PLT stub at `plt_off` with a direct jump to `target`, and pads the rest of the
+/// stub with nops.
+fn redirect_plt_stub(
+    data: &mut [u8],
+    arch: SurgeryArch,
+    plt_off: usize,
+    plt_vaddr: u64,
+    plt_size: usize,
+    target: u64,
+    verbose: bool,
+) {
+    if verbose {
+        println!("\tPLT: {plt_off:+x}, {plt_vaddr:+x}");
+    }
+    match arch {
+        SurgeryArch::X86_64 => {
+            let jmp_inst_len = 5;
+            let target = (target as i64 - (plt_vaddr as i64 + jmp_inst_len as i64)) as i32;
+            if verbose {
+                println!("\tTarget Jump: {target:+x}");
+            }
+            data[plt_off] = 0xE9;
+            data[plt_off + 1..plt_off + jmp_inst_len].copy_from_slice(&target.to_le_bytes());
+            for i in jmp_inst_len..plt_size {
+                data[plt_off + i] = 0x90;
+            }
+        }
+        SurgeryArch::AArch64 => {
+            // Keep a leading `bti c`, so the stub stays a valid target for indirect calls.
+            let mut branch_off = plt_off;
+            if aarch64::read(data, plt_off) == aarch64::BTI_C {
+                branch_off += 4;
+            }
+            let branch_vaddr = plt_vaddr + (branch_off - plt_off) as u64;
+            if verbose {
+                println!("\tTarget Branch: {target:+x}");
+            }
+            aarch64::write(data, branch_off, aarch64::B);
+            aarch64::write_relocation(
+                data,
+                branch_off,
+                aarch64::Relocation::Branch26,
+                branch_vaddr,
+                target,
+            );
+            for nop_off in (branch_off + 4..plt_off + plt_size).step_by(4) {
+                aarch64::write(data, nop_off, aarch64::NOP);
+            }
+        }
+    }
+}
+
+/// The alignment to place an app section at: what the section asks for, but at least
+/// `MIN_SECTION_ALIGNMENT`.
+fn section_alignment(sec: &Section) -> usize {
+    MIN_SECTION_ALIGNMENT.max(sec.align() as usize)
+}
+
+/// Strips the version suffix from a shared library name, e.g. `libapp.so.1.2` becomes `libapp.so`.
+fn soname_base(name: &str) -> &str {
+    let mut base = name;
+    while let Some((rest, suffix)) = base.rsplit_once('.') {
+        if suffix.is_empty() || !suffix.bytes().all(|b| b.is_ascii_digit()) {
+            break;
+        }
+        base = rest;
+    }
+    base
+}
+
+/// Writes a resolved relocation of `size` bits at `base`. A `signed` 32 bit relocation is sign
+/// extended when it is used, so its target must fit in an `i32` rather than a `u32`.
+/// A target that does not fit in the relocation is an error rather than silently truncated.
+fn write_relocation(data: &mut [u8], base: usize, size: u8, signed: bool, target: i64) {
+    match size {
+        32 => {
+            let bytes = if signed {
+                i32::try_from(target).map(i32::to_le_bytes)
+            } else {
+                u32::try_from(target).map(u32::to_le_bytes)
+            };
+            let bytes = bytes.unwrap_or_else(|_| {
+                internal_error!("Relocation target {target:+x} does not fit in 32 bits")
+            });
+            data[base..][..4].copy_from_slice(&bytes);
+        }
+        64 => {
+            data[base..][..8].copy_from_slice(&target.to_le_bytes());
+        }
+        other => {
+            internal_error!("Relocation size not yet supported: {other}");
+        }
+    }
+}
+
+/// Rewrites a RIP-relative `mov reg, [rip + disp32]` that ends right before `base` into
+/// `lea reg, [rip + disp32]`, so it no longer loads the address from a GOT slot.
+/// Returns false and leaves the bytes untouched if the instruction is not such a `mov`.
+fn relax_got_load(data: &mut [u8], base: usize) -> bool {
+    if base < 2 {
+        return false;
+    }
+
+    let opcode = base - 2;
+    let modrm = data[base - 1];
+    // mod == 0b00 and rm == 0b101 is the RIP-relative addressing form.
+    if data[opcode] == 0x8b && modrm & 0xc7 == 0x05 {
+        data[opcode] = 0x8d;
+        true
+    } else {
+        false
+    }
+}
+
 pub(crate) fn surgery_elf(
     roc_app_bytes: &[u8],
     metadata_path: &Path,
     executable_path: &Path,
+    max_size: Option<u64>,
+    strip_sections: bool,
     verbose: bool,
     time: bool,
-) {
+) -> Result<(), LinkError> {
+    surgery_elf_with(
+        roc_app_bytes,
+        || Metadata::read_from_file(metadata_path),
+        executable_path,
+        max_size,
+        strip_sections,
+        verbose,
+        time,
+    )
+}
+
+/// Preprocesses the host into `output_path` and links the app into it in one go. The metadata
+/// is handed to surgery directly rather than written to disk and read back.
+#[allow(clippy::too_many_arguments)]
+pub(crate) fn link_elf(
+    endianness: target_lexicon::Endianness,
+    host_exe_path: &Path,
+    shared_lib: &Path,
+    app_function_names: &[String],
+    roc_app_bytes: &[u8],
+    output_path: &Path,
+    max_size: Option<u64>,
+    strip_sections: bool,
+    verbose: bool,
+    time: bool,
+) -> Result<(), LinkError> {
+    let md = preprocess_elf(
+        endianness,
+        host_exe_path,
+        None,
+        output_path,
+        shared_lib,
+        app_function_names,
+        false,
+        verbose,
+        time,
+    )?;
+
+    surgery_elf_with(
+        roc_app_bytes,
+        || md,
+        output_path,
+        max_size,
+        strip_sections,
+        verbose,
+        time,
+    )
+}
+
+fn surgery_elf_with(
+    roc_app_bytes: &[u8],
+    load_metadata: impl FnOnce() -> Metadata,
+    executable_path: &Path,
+    max_size: Option<u64>,
+    strip_sections: bool,
+    verbose: bool,
+    time: bool,
+) -> Result<(), LinkError> {
     let app_obj = match object::File::parse(roc_app_bytes) {
         Ok(obj) => obj,
         Err(err) => {
@@ -1156,26 +1896,28 @@ pub(crate) fn surgery_elf(
         }
     };
 
-    if app_obj
-        .sections()
-        .filter(|sec| {
-            let name = sec.name().unwrap_or_default();
-            !name.starts_with(".debug") && !name.starts_with(".eh")
-        })
-        .flat_map(|sec| sec.relocations())
-        .any(|(_, reloc)| reloc.kind() == RelocationKind::Absolute)
+    let total_start = Instant::now();
+
+    let loading_metadata_start = total_start;
+    let md = load_metadata();
+    let loading_metadata_duration = loading_metadata_start.elapsed();
+
+    // Absolute addresses are only final when the host is not position-independent.
+    if md.e_type != elf::ET_EXEC
+        && app_obj
+            .sections()
+            .filter(|sec| {
+                let name = sec.name().unwrap_or_default();
+                !name.starts_with(".debug") && !name.starts_with(".eh")
+            })
+            .flat_map(|sec| sec.relocations())
+            .any(|(_, reloc)| reloc.kind() == RelocationKind::Absolute)
     {
         eprintln!("The surgical linker currently has issue #3609 and would fail linking your app.");
         eprintln!("Please use `--linker=legacy` to avoid the issue for now.");
         std::process::exit(1);
     }
 
-    let total_start = Instant::now();
-
-    let loading_metadata_start = total_start;
-    let md = Metadata::read_from_file(metadata_path);
-    let loading_metadata_duration = loading_metadata_start.elapsed();
-
     let load_and_mmap_start = Instant::now();
     let max_out_len = md.exec_len + roc_app_bytes.len() as u64 + md.load_align_constraint;
     let mut exec_mmap = open_mmap_mut(executable_path, max_out_len as usize);
@@ -1184,9 +1926,27 @@ pub(crate) fn surgery_elf(
     let out_gen_start = Instant::now();
     let mut offset = 0;
 
-    surgery_elf_help(verbose, &md, &mut exec_mmap, &mut offset, app_obj);
+    let result = surgery_elf_help(
+        verbose,
+        &md,
+        &mut exec_mmap,
+        &mut offset,
+        app_obj,
+        strip_sections,
+    )
+    .and_then(|()| check_size_budget(offset as u64, max_size));
 
     let out_gen_duration = out_gen_start.elapsed();
+
+    if let Err(err) = result {
+        // The output was already sized for the worst case, so remove it rather than
+        // leaving a partially written executable behind.
+        drop(exec_mmap);
+        std::fs::remove_file(executable_path)?;
+
+        return Err(err);
+    }
+
     let flushing_data_start = Instant::now();
 
     // TODO investigate using the async version of flush - might be faster due to not having to block on that
@@ -1196,6 +1956,13 @@ pub(crate) fn surgery_elf(
     // Also drop files to to ensure data is fully written here.
     drop(exec_mmap);
 
+    // The file was sized for the worst case, so cut off the unused bytes past the section headers.
+    std::fs::OpenOptions::new()
+        .write(true)
+        .open(executable_path)
+        .and_then(|file| file.set_len(offset as u64))
+        .unwrap_or_else(|e| internal_error!("{}", e));
+
     let flushing_data_duration = flushing_data_start.elapsed();
 
     // Make sure the final executable has permision to execute.
@@ -1228,6 +1995,8 @@ pub(crate) fn surgery_elf(
         report_timing("Other", total_duration.saturating_sub(sum));
         report_timing("Total", total_duration);
     }
+
+    Ok(())
 }
 
 fn surgery_elf_help(
@@ -1236,7 +2005,8 @@ fn surgery_elf_help(
     exec_mmap: &mut MmapMut,
     offset_ref: &mut usize, // TODO return this instead of taking a mutable reference to it
     app_obj: object::File,
-) {
+    strip_sections: bool,
+) -> Result<(), LinkError> {
     let elf64 = exec_mmap[4] == 2;
     let litte_endian = exec_mmap[5] == 1;
     if !elf64 || !litte_endian {
@@ -1314,7 +2084,18 @@ fn surgery_elf_help(
         .chain(bss_sections.iter())
         .chain(text_sections.iter())
     {
-        offset = align_by_constraint(offset, MIN_SECTION_ALIGNMENT);
+        let alignment = section_alignment(sec);
+        if alignment > md.load_align_constraint as usize {
+            internal_error!(
+                "Section {} needs an alignment of {:+x}, more than the host's load alignment of {:+x}",
+                sec.name().unwrap_or_default(),
+                alignment,
+                md.load_align_constraint
+            );
+        }
+        offset = align_by_constraint(offset, alignment);
+        // Since the load alignment is a multiple of the section alignment, this keeps the
+        // virtual address aligned as well.
         virt_offset =
             align_to_offset_by_constraint(virt_offset, offset, md.load_align_constraint as usize);
         if verbose {
@@ -1455,9 +2236,58 @@ fn surgery_elf_help(
                     if let Some(target_offset) = target_offset {
                         let virt_base = section_virtual_offset + rel.0 as usize;
                         let base = section_offset + rel.0 as usize;
-                        let target: i64 = match rel.1.kind() {
-                            RelocationKind::Relative | RelocationKind::PltRelative => {
-                                target_offset - virt_base as i64 + rel.1.addend()
+
+                        // AArch64 relocations in code patch a field of the instruction.
+                        let aarch64_relocation = match md.arch {
+                            SurgeryArch::X86_64 => None,
+                            SurgeryArch::AArch64 => aarch64::Relocation::from_object(&rel.1),
+                        };
+                        if let Some(relocation) = aarch64_relocation {
+                            let target = (target_offset + rel.1.addend()) as u64;
+                            if verbose {
+                                println!(
+                                    "\t\tRelocation base location: {base:+x} (virt: {virt_base:+x})",
+                                );
+                                println!("\t\tFinal relocation target: {target:+x}");
+                            }
+                            aarch64::write_relocation(
+                                exec_mmap,
+                                base,
+                                relocation,
+                                virt_base as u64,
+                                target,
+                            );
+                            continue;
+                        }
+
+                        let (target, size, signed): (i64, u8, bool) = match rel.1.kind() {
+                            RelocationKind::Relative | RelocationKind::PltRelative => (
+                                target_offset - virt_base as i64 + rel.1.addend(),
+                                rel.1.size(),
+                                true,
+                            ),
+                            // Only reached for hosts that are not position-independent.
+                            // `R_X86_64_32` is zero extended, and `R_X86_64_32S` sign extended.
+                            RelocationKind::Absolute => (
+                                target_offset + rel.1.addend(),
+                                rel.1.size(),
+                                rel.1.encoding() == RelocationEncoding::X86Signed,
+                            ),
+                            // We do not build a GOT for the app, but a relaxable GOT load can
+                            // compute the address directly instead of going through a slot.
+                            RelocationKind::Elf(
+                                elf::R_X86_64_GOTPCRELX | elf::R_X86_64_REX_GOTPCRELX,
+                            ) if md.arch == SurgeryArch::X86_64 => {
+                                if !relax_got_load(exec_mmap, base) {
+                                    internal_error!(
+                                        "GOT load can not be relaxed, only `mov reg, [rip + disp32]` is supported: {:+x?}",
+                                        rel
+                                    );
+                                }
+                                if verbose {
+                                    println!("\t\tRelaxed GOT load to lea");
+                                }
+                                (target_offset - virt_base as i64 + rel.1.addend(), 32, true)
                             }
                             x => {
                                 internal_error!("Relocation Kind not yet support: {:?}", x);
@@ -1469,19 +2299,7 @@ fn surgery_elf_help(
                             );
                             println!("\t\tFinal relocation target offset: {target:+x}");
                         }
-                        match rel.1.size() {
-                            32 => {
-                                let data = (target as i32).to_le_bytes();
-                                exec_mmap[base..][..4].copy_from_slice(&data);
-                            }
-                            64 => {
-                                let data = target.to_le_bytes();
-                                exec_mmap[base..][..8].copy_from_slice(&data);
-                            }
-                            other => {
-                                internal_error!("Relocation size not yet supported: {other}");
-                            }
-                        }
+                        write_relocation(exec_mmap, base, size, signed, target);
                     } else {
                         internal_error!(
                             "Undefined Symbol in relocation, {:+x?}: {:+x?}",
@@ -1500,8 +2318,10 @@ fn surgery_elf_help(
 
     offset = align_by_constraint(offset, MIN_SECTION_ALIGNMENT);
     let new_sh_offset = offset;
-    exec_mmap[offset..][..sh_size].copy_from_slice(&sh_tab);
-    offset += sh_size;
+    if !strip_sections {
+        exec_mmap[offset..][..sh_size].copy_from_slice(&sh_tab);
+        offset += sh_size;
+    }
 
     // Flush app only data to speed up write to disk.
     exec_mmap
@@ -1515,65 +2335,89 @@ fn surgery_elf_help(
 
     // Add 3 new sections and segments.
     let new_section_count = 3;
-    offset += new_section_count * sh_ent_size as usize;
-    let section_headers = load_structs_inplace_mut::<elf::SectionHeader64<LE>>(
-        exec_mmap,
-        new_sh_offset,
-        sh_num as usize + new_section_count,
-    );
+    if !strip_sections {
+        offset += new_section_count * sh_ent_size as usize;
+    }
 
     let new_rodata_section_size = new_text_section_offset - new_rodata_section_offset;
     let new_bss_section_virtual_size = new_text_section_vaddr - new_bss_section_vaddr;
     let new_text_section_size = new_sh_offset as u64 - new_text_section_offset;
 
-    // set the new rodata section header
-    section_headers[section_headers.len() - 3] = elf::SectionHeader64 {
-        sh_name: endian::U32::new(LE, 0),
-        sh_type: endian::U32::new(LE, elf::SHT_PROGBITS),
-        sh_flags: endian::U64::new(LE, elf::SHF_ALLOC as u64),
-        sh_addr: endian::U64::new(LE, new_rodata_section_vaddr),
-        sh_offset: endian::U64::new(LE, new_rodata_section_offset),
-        sh_size: endian::U64::new(LE, new_rodata_section_size),
-        sh_link: endian::U32::new(LE, 0),
-        sh_info: endian::U32::new(LE, 0),
-        sh_addralign: endian::U64::new(LE, 16),
-        sh_entsize: endian::U64::new(LE, 0),
-    };
+    if verbose {
+        println!();
+        println!("Output Size Breakdown");
+        println!("\tPlatform: {} bytes", md.exec_len);
+        println!("\tApp read-only data: {new_rodata_section_size} bytes");
+        println!(
+            "\tApp zero-initialized data: {new_bss_section_virtual_size} bytes (in memory only)"
+        );
+        println!("\tApp code: {new_text_section_size} bytes");
+        println!("\tSection headers: {} bytes", offset - new_sh_offset);
+        println!("\tTotal: {offset} bytes");
+    }
 
-    // set the new bss section header
-    section_headers[section_headers.len() - 2] = elf::SectionHeader64 {
-        sh_name: endian::U32::new(LE, 0),
-        sh_type: endian::U32::new(LE, elf::SHT_NOBITS),
-        sh_flags: endian::U64::new(LE, (elf::SHF_ALLOC) as u64),
-        sh_addr: endian::U64::new(LE, new_bss_section_vaddr),
-        sh_offset: endian::U64::new(LE, new_bss_section_offset),
-        sh_size: endian::U64::new(LE, new_bss_section_virtual_size),
-        sh_link: endian::U32::new(LE, 0),
-        sh_info: endian::U32::new(LE, 0),
-        sh_addralign: endian::U64::new(LE, 16),
-        sh_entsize: endian::U64::new(LE, 0),
-    };
+    if strip_sections {
+        // Nothing needs the section headers at runtime, so leave the table out entirely.
+        let file_header = load_struct_inplace_mut::<elf::FileHeader64<LE>>(exec_mmap, 0);
+        file_header.e_shoff.set(LE, 0);
+        file_header.e_shnum.set(LE, 0);
+        file_header.e_shstrndx.set(LE, elf::SHN_UNDEF);
+    } else {
+        let section_headers = load_structs_inplace_mut::<elf::SectionHeader64<LE>>(
+            exec_mmap,
+            new_sh_offset,
+            sh_num as usize + new_section_count,
+        );
 
-    // set the new text section header
-    section_headers[section_headers.len() - 1] = elf::SectionHeader64 {
-        sh_name: endian::U32::new(LE, 0),
-        sh_type: endian::U32::new(LE, elf::SHT_PROGBITS),
-        sh_flags: endian::U64::new(LE, (elf::SHF_ALLOC | elf::SHF_EXECINSTR) as u64),
-        sh_addr: endian::U64::new(LE, new_text_section_vaddr),
-        sh_offset: endian::U64::new(LE, new_text_section_offset),
-        sh_size: endian::U64::new(LE, new_text_section_size),
-        sh_link: endian::U32::new(LE, 0),
-        sh_info: endian::U32::new(LE, 0),
-        sh_addralign: endian::U64::new(LE, 16),
-        sh_entsize: endian::U64::new(LE, 0),
-    };
+        // set the new rodata section header
+        section_headers[section_headers.len() - 3] = elf::SectionHeader64 {
+            sh_name: endian::U32::new(LE, 0),
+            sh_type: endian::U32::new(LE, elf::SHT_PROGBITS),
+            sh_flags: endian::U64::new(LE, elf::SHF_ALLOC as u64),
+            sh_addr: endian::U64::new(LE, new_rodata_section_vaddr),
+            sh_offset: endian::U64::new(LE, new_rodata_section_offset),
+            sh_size: endian::U64::new(LE, new_rodata_section_size),
+            sh_link: endian::U32::new(LE, 0),
+            sh_info: endian::U32::new(LE, 0),
+            sh_addralign: endian::U64::new(LE, 16),
+            sh_entsize: endian::U64::new(LE, 0),
+        };
 
-    // Reload and update file header and size.
-    let file_header = load_struct_inplace_mut::<elf::FileHeader64<LE>>(exec_mmap, 0);
-    file_header.e_shoff.set(LE, new_sh_offset as u64);
-    file_header
-        .e_shnum
-        .set(LE, sh_num + new_section_count as u16);
+        // set the new bss section header
+        section_headers[section_headers.len() - 2] = elf::SectionHeader64 {
+            sh_name: endian::U32::new(LE, 0),
+            sh_type: endian::U32::new(LE, elf::SHT_NOBITS),
+            sh_flags: endian::U64::new(LE, (elf::SHF_ALLOC) as u64),
+            sh_addr: endian::U64::new(LE, new_bss_section_vaddr),
+            sh_offset: endian::U64::new(LE, new_bss_section_offset),
+            sh_size: endian::U64::new(LE, new_bss_section_virtual_size),
+            sh_link: endian::U32::new(LE, 0),
+            sh_info: endian::U32::new(LE, 0),
+            sh_addralign: endian::U64::new(LE, 16),
+            sh_entsize: endian::U64::new(LE, 0),
+        };
+
+        // set the new text section header
+        section_headers[section_headers.len() - 1] = elf::SectionHeader64 {
+            sh_name: endian::U32::new(LE, 0),
+            sh_type: endian::U32::new(LE, elf::SHT_PROGBITS),
+            sh_flags: endian::U64::new(LE, (elf::SHF_ALLOC | elf::SHF_EXECINSTR) as u64),
+            sh_addr: endian::U64::new(LE, new_text_section_vaddr),
+            sh_offset: endian::U64::new(LE, new_text_section_offset),
+            sh_size: endian::U64::new(LE, new_text_section_size),
+            sh_link: endian::U32::new(LE, 0),
+            sh_info: endian::U32::new(LE, 0),
+            sh_addralign: endian::U64::new(LE, 16),
+            sh_entsize: endian::U64::new(LE, 0),
+        };
+
+        // Reload and update file header and size.
+        let file_header = load_struct_inplace_mut::<elf::FileHeader64<LE>>(exec_mmap, 0);
+        file_header.e_shoff.set(LE, new_sh_offset as u64);
+        file_header
+            .e_shnum
+            .set(LE, sh_num + new_section_count as u16);
+    }
 
     // Add 2 new segments that match the new sections.
     let program_headers = load_structs_inplace_mut::<elf::ProgramHeader64<LE>>(
@@ -1626,18 +2470,7 @@ fn surgery_elf_help(
     for func_name in md.app_functions.iter() {
         let func_virt_offset = match app_func_vaddr_map.get(func_name) {
             Some(offset) => *offset as u64,
-            None => {
-                eprintln!("Error:");
-                eprintln!("\n\tFunction, {}, was not defined by the app.", &func_name);
-                eprintln!("\nPotential causes:");
-                eprintln!("\n\t- because the platform was built with a non-compatible version of roc compared to the one you are running.");
-                eprintln!("\n\t\tsolutions:");
-                eprintln!("\t\t\t+ Downgrade your roc version to the one that was used to build the platform.");
-                eprintln!("\t\t\t+ Or ask the platform author to release a new version of the platform using a current roc release.");
-                eprintln!("\n\t- This can also occur due to a bug in the compiler. In that case, file an issue here: https://github.com/roc-lang/roc/issues/new/choose");
-
-                std::process::exit(1);
-            }
+            None => return Err(LinkError::MissingSymbol(func_name.clone())),
         };
         if verbose {
             println!(
@@ -1650,53 +2483,23 @@ fn surgery_elf_help(
             if verbose {
                 println!("\tPerforming surgery: {s:+x?}");
             }
-            let surgery_virt_offset = match s.virtual_offset {
-                VirtualOffset::Relative(vs) => (vs + md.added_byte_count) as i64,
-                VirtualOffset::Absolute => 0,
-            };
-            match s.size {
-                4 => {
-                    let target = (func_virt_offset as i64 - surgery_virt_offset) as i32;
-                    if verbose {
-                        println!("\tTarget Jump: {target:+x}");
-                    }
-                    let data = target.to_le_bytes();
-                    exec_mmap[(s.file_offset + md.added_byte_count) as usize..][..4]
-                        .copy_from_slice(&data);
-                }
-                8 => {
-                    let target = func_virt_offset as i64 - surgery_virt_offset;
-                    if verbose {
-                        println!("\tTarget Jump: {target:+x}");
-                    }
-                    let data = target.to_le_bytes();
-                    exec_mmap[(s.file_offset + md.added_byte_count) as usize..][..8]
-                        .copy_from_slice(&data);
-                }
-                x => {
-                    internal_error!("Surgery size not yet supported: {}", x);
-                }
-            }
+            perform_surgery(exec_mmap, md, s, func_virt_offset, verbose);
         }
 
         // Replace plt call code with just a jump.
         // This is a backup incase we missed a call to the plt.
-        if let Some((plt_off, plt_vaddr)) = md.plt_addresses.get(func_name) {
+        if let Some((plt_off, plt_vaddr, plt_size)) = md.plt_addresses.get(func_name) {
             let plt_off = (*plt_off + md.added_byte_count) as usize;
             let plt_vaddr = *plt_vaddr + md.added_byte_count;
-            let jmp_inst_len = 5;
-            let target =
-                (func_virt_offset as i64 - (plt_vaddr as i64 + jmp_inst_len as i64)) as i32;
-            if verbose {
-                println!("\tPLT: {plt_off:+x}, {plt_vaddr:+x}");
-                println!("\tTarget Jump: {target:+x}");
-            }
-            let data = target.to_le_bytes();
-            exec_mmap[plt_off] = 0xE9;
-            exec_mmap[plt_off + 1..plt_off + jmp_inst_len].copy_from_slice(&data);
-            for i in jmp_inst_len..PLT_ADDRESS_OFFSET as usize {
-                exec_mmap[plt_off + i] = 0x90;
-            }
+            redirect_plt_stub(
+                exec_mmap,
+                md.arch,
+                plt_off,
+                plt_vaddr,
+                *plt_size as usize,
+                func_virt_offset,
+                verbose,
+            );
         }
 
         if let Some(i) = md.dynamic_symbol_indices.get(func_name) {
@@ -1735,6 +2538,8 @@ fn surgery_elf_help(
 
     // TODO return this instead of accepting a mutable ref!
     *offset_ref = offset;
+
+    Ok(())
 }
 
 #[cfg(test)]
@@ -1799,67 +2604,711 @@ mod tests {
         )
     }
 
-    #[allow(dead_code)]
-    fn zig_host_app_help(dir: &Path, target: &Triple) {
-        let host_zig = indoc!(
-            r#"
-            const std = @import("std");
-
-            extern fn roc_magic1(usize) callconv(.C) [*]const u8;
+    #[test]
+    fn allowlisted_app_functions_elf() {
+        let object = object::File::parse(ELF64_DYNHOST).unwrap();
 
-            pub fn main() !void {
-                const stdout = std.io.getStdOut().writer();
-                try stdout.print("Hello {s}\n", .{roc_magic1(0)[0..3]});
-            }
-            "#
-        );
+        let mut keys: Vec<_> = object
+            .dynamic_symbols()
+            .filter(|sym| is_app_function(sym, &[String::from("clock_gettime")]))
+            .filter_map(|s| s.name().ok())
+            .collect();
+        keys.sort_unstable();
 
-        let app_zig = indoc!(
-            r#"
-            const X = [_][]const u8 { "foo" };
+        assert_eq!(
+            [
+                "clock_gettime",
+                "roc__mainForHost_1__Fx_caller",
+                "roc__mainForHost_1__Fx_result_size",
+                "roc__mainForHost_1_exposed_generic",
+                "roc__mainForHost_size"
+            ],
+            keys.as_slice()
+        )
+    }
 
-            export fn roc_magic1(index: usize) [*]const u8 {
-                return X[index].ptr;
-            }
-            "#
-        );
+    #[test]
+    fn stub_lib_that_is_not_an_object_is_outdated() {
+        use std::str::FromStr;
 
-        let zig = std::env::var("ROC_ZIG").unwrap_or_else(|_| "zig".into());
+        let dir = tempfile::tempdir().unwrap();
+        let stub_lib = dir.path().join("libapp.so");
+        std::fs::write(&stub_lib, b"not an object file").unwrap();
+
+        let target = Triple::from_str("x86_64-unknown-linux-gnu").unwrap();
+        assert!(!crate::stub_lib_is_up_to_date(
+            &target,
+            &stub_lib,
+            &[String::from("roc__mainForHost_1_exposed_generic")]
+        ));
+    }
 
-        std::fs::write(dir.join("host.zig"), host_zig.as_bytes()).unwrap();
-        std::fs::write(dir.join("app.zig"), app_zig.as_bytes()).unwrap();
+    #[test]
+    fn warn_on_non_pie_host() {
+        let e_type = |data: &[u8]| load_struct_inplace::<elf::FileHeader64<LE>>(data, 0).e_type;
 
-        // we need to compile the app first
-        let output = std::process::Command::new(&zig)
-            .current_dir(dir)
-            .args(["build-obj", "app.zig", "-fPIC", "-OReleaseFast"])
-            .output()
-            .unwrap();
+        assert_eq!(non_pie_warning(e_type(ELF64_DYNHOST).get(LE)), None);
 
-        if !output.status.success() {
-            use std::io::Write;
+        let mut non_pie = ELF64_DYNHOST.to_vec();
+        load_struct_inplace_mut::<elf::FileHeader64<LE>>(&mut non_pie, 0)
+            .e_type
+            .set(LE, elf::ET_EXEC);
 
-            std::io::stdout().write_all(&output.stdout).unwrap();
-            std::io::stderr().write_all(&output.stderr).unwrap();
+        let warning = non_pie_warning(e_type(&non_pie).get(LE)).unwrap();
+        assert!(warning.contains("not position-independent"));
+    }
 
-            panic!("zig build-obj failed");
+    #[test]
+    fn delete_dynamic_entry_keeps_terminator() {
+        let entries = [
+            (elf::DT_NEEDED, 1),
+            (elf::DT_NEEDED, 2),
+            (elf::DT_STRTAB, 3),
+            (elf::DT_NULL, 0),
+        ];
+        let mut data = vec![0xffu8; 8];
+        for (tag, val) in entries {
+            data.extend_from_slice(&(tag as u64).to_le_bytes());
+            data.extend_from_slice(&(val as u64).to_le_bytes());
         }
 
-        // open our app object; we'll copy sections from it later
-        let file = std::fs::File::open(dir.join("app.o")).unwrap();
-        let roc_app = unsafe { memmap2::Mmap::map(&file) }.unwrap();
+        delete_dynamic_entry(&mut data, 8, 3, 1);
 
-        let names: Vec<String> = {
-            let object = object::File::parse(&*roc_app).unwrap();
+        let table = crate::load_structs_inplace::<elf::Dyn64<LE>>(&data, 8, entries.len());
+        let table: Vec<_> = table
+            .iter()
+            .map(|entry| (entry.d_tag.get(LE), entry.d_val.get(LE)))
+            .collect();
 
-            object
-                .symbols()
-                .filter(|s| !s.is_local())
-                .map(|e| e.name().unwrap().to_string())
-                .collect()
-        };
+        assert_eq!(
+            table,
+            [
+                (elf::DT_NEEDED as u64, 1),
+                (elf::DT_STRTAB as u64, 3),
+                (elf::DT_NULL as u64, 0),
+                (elf::DT_NULL as u64, 0),
+            ]
+        );
+        // Bytes before the table are untouched.
+        assert_eq!(data[..8], [0xff; 8]);
+    }
 
-        let dylib_bytes = crate::generate_dylib::create_dylib_elf64(&names).unwrap();
+    #[test]
+    fn scan_text_section_finds_app_calls() {
+        // call roc_foo; call [rip + 0]
+        let data = [0xe8, 0, 0, 0, 0, 0xff, 0x15, 0, 0, 0, 0];
+        let sec = TextSection {
+            description: String::from(".text"),
+            data: Cow::Borrowed(&data[..]),
+            address: 0x1000,
+            file_offset: 0x200,
+            compressed: false,
+        };
+        let mut app_func_addresses = MutMap::default();
+        app_func_addresses.insert(0x1005, "roc_foo");
+
+        let (entries, found_indirect) =
+            scan_text_section(SurgeryArch::X86_64, &app_func_addresses, &sec);
+
+        assert_eq!(
+            entries,
+            [(
+                "roc_foo",
+                SurgeryEntry {
+                    file_offset: 0x201,
+                    virtual_offset: VirtualOffset::Relative(0x1005),
+                    size: 4,
+                }
+            )]
+        );
+        assert!(found_indirect);
+    }
+
+    #[test]
+    fn split_text_sections_at_functions() {
+        let data = [0x90; 100];
+        let sections = [TextSection {
+            description: String::from(".text"),
+            data: Cow::Borrowed(&data[..]),
+            address: 0x1000,
+            file_offset: 0x200,
+            compressed: false,
+        }];
+        let split_points = TextSplitPoints {
+            arch: SurgeryArch::X86_64,
+            functions: vec![0x100a, 0x1028, 0x102d, 0x105a],
+            // Covers the function at 0x1028, so it can not be split there.
+            relocations: vec![(0x1026, 0x102a)],
+        };
+
+        let chunks: Vec<_> = split_text_sections(&sections, &split_points, 4)
+            .iter()
+            .map(|chunk| (chunk.address, chunk.file_offset, chunk.data.len()))
+            .collect();
+
+        assert_eq!(
+            chunks,
+            [
+                (0x1000, 0x200, 45),
+                (0x102d, 0x22d, 45),
+                (0x105a, 0x25a, 10)
+            ]
+        );
+    }
+
+    #[test]
+    fn split_text_sections_at_words() {
+        let data = [0; 40];
+        let sections = [TextSection {
+            description: String::from(".text"),
+            data: Cow::Borrowed(&data[..]),
+            address: 0x1000,
+            file_offset: 0x200,
+            compressed: false,
+        }];
+        let split_points = TextSplitPoints {
+            arch: SurgeryArch::AArch64,
+            functions: vec![],
+            relocations: vec![],
+        };
+
+        let chunks: Vec<_> = split_text_sections(&sections, &split_points, 3)
+            .iter()
+            .map(|chunk| (chunk.address, chunk.data.len()))
+            .collect();
+
+        // Chunks of 14 bytes, rounded up to whole instructions.
+        assert_eq!(chunks, [(0x1000, 16), (0x1010, 16), (0x1020, 8)]);
+    }
+
+    #[test]
+    fn eight_byte_surgery() {
+        let mut md = Metadata {
+            added_byte_count: 0x40,
+            ..Default::default()
+        };
+        md.surgeries.insert(
+            "roc__mainForHost_1_exposed_generic".to_string(),
+            vec![SurgeryEntry {
+                file_offset: 0x8,
+                virtual_offset: VirtualOffset::Relative(0x1010),
+                size: 8,
+            }],
+        );
+
+        // Surgeries come from the metadata file, so make sure they survive the round trip.
+        let dir = tempfile::tempdir().unwrap();
+        let metadata_path = dir.path().join("metadata");
+        md.write_to_file(&metadata_path);
+        let md = Metadata::read_from_file(&metadata_path);
+
+        let mut data = vec![0u8; 0x60];
+        for s in md.surgeries["roc__mainForHost_1_exposed_generic"].iter() {
+            perform_surgery(&mut data, &md, s, 0x2_0000_1000, false);
+        }
+
+        let expected = 0x2_0000_1000i64 - 0x1050;
+        assert_eq!(data[0x48..0x50], expected.to_le_bytes());
+        assert!(data[..0x48].iter().chain(&data[0x50..]).all(|b| *b == 0));
+    }
+
+    /// Encodes `jmp qword ptr [rip + disp]` at `ip` (after `prefix`) that jumps through `got_slot`.
+    fn indirect_jmp(prefix: &[u8], ip: u64, got_slot: u64) -> Vec<u8> {
+        let mut bytes = prefix.to_vec();
+        bytes.extend([0xff, 0x25]);
+        let next_ip = ip + bytes.len() as u64 + 4;
+        bytes.extend(((got_slot as i64 - next_ip as i64) as i32).to_le_bytes());
+        bytes
+    }
+
+    #[test]
+    fn plt_sec_stubs() {
+        // endbr64; bnd jmp [rip + disp]; nop dword [rax + rax]
+        let endbr64 = [0xf3, 0x0f, 0x1e, 0xfa];
+        let nop5 = [0x0f, 0x1f, 0x44, 0x00, 0x00];
+        let mut data = vec![];
+        for (i, got_slot) in [0x3000, 0x3008].into_iter().enumerate() {
+            let ip = 0x1000 + 16 * i as u64 + endbr64.len() as u64;
+            data.extend(endbr64);
+            data.extend(indirect_jmp(&[0xf2], ip, got_slot));
+            data.extend(nop5);
+        }
+
+        assert_eq!(
+            plt_stubs(&data, 0x1000),
+            [
+                PltStub {
+                    address: 0x1000,
+                    size: 16,
+                    got_slot: 0x3000
+                },
+                PltStub {
+                    address: 0x1010,
+                    size: 16,
+                    got_slot: 0x3008
+                },
+            ]
+        );
+    }
+
+    #[test]
+    fn plt_got_stubs() {
+        // jmp [rip + disp]; xchg ax, ax
+        let mut data = vec![];
+        for (i, got_slot) in [0x3000, 0x3010].into_iter().enumerate() {
+            data.extend(indirect_jmp(&[], 0x1000 + 8 * i as u64, got_slot));
+            data.extend([0x66, 0x90]);
+        }
+
+        assert_eq!(
+            plt_stubs(&data, 0x1000),
+            [
+                PltStub {
+                    address: 0x1000,
+                    size: 8,
+                    got_slot: 0x3000
+                },
+                PltStub {
+                    address: 0x1008,
+                    size: 8,
+                    got_slot: 0x3010
+                },
+            ]
+        );
+    }
+
+    #[test]
+    fn lazy_plt_stubs() {
+        // jmp [rip + disp]; push 0; jmp .plt
+        let mut data = indirect_jmp(&[], 0x1000, 0x3018);
+        data.extend([0x68, 0, 0, 0, 0]);
+        data.extend([0xe9, 0xea, 0xff, 0xff, 0xff]);
+
+        assert_eq!(
+            plt_stubs(&data, 0x1000),
+            [PltStub {
+                address: 0x1000,
+                size: 16,
+                got_slot: 0x3018
+            }]
+        );
+    }
+
+    fn aarch64_words(words: &[u32]) -> Vec<u8> {
+        words.iter().flat_map(|word| word.to_le_bytes()).collect()
+    }
+
+    #[test]
+    fn aarch64_plt_stubs() {
+        let data = aarch64_words(&[
+            // .plt header: stp x16, x30, [sp, #-16]!; adrp x16, 0x11000; ldr x17, [x16, #0xff8];
+            // add x16, x16, #0xff8; br x17; nop; nop; nop
+            0xa9bf7bf0, 0xb0000090, 0xf947fe11, 0x913fe210, 0xd61f0220, 0xd503201f, 0xd503201f,
+            0xd503201f,
+            // adrp x16, 0x11000; ldr x17, [x16]; add x16, x16, #0; br x17
+            0xb0000090, 0xf9400211, 0x91000210, 0xd61f0220,
+            // bti c; adrp x16, 0x11000; ldr x17, [x16, #8]; add x16, x16, #8; br x17
+            0xd503245f, 0xb0000090, 0xf9400611, 0x91002210, 0xd61f0220,
+        ]);
+
+        assert_eq!(
+            plt_stubs(SurgeryArch::AArch64, &data, 0x600),
+            [
+                PltStub {
+                    address: 0x604,
+                    size: 0x1c,
+                    got_slot: 0x11ff8
+                },
+                PltStub {
+                    address: 0x620,
+                    size: 0x10,
+                    got_slot: 0x11000
+                },
+                PltStub {
+                    address: 0x630,
+                    size: 0x14,
+                    got_slot: 0x11008
+                },
+            ]
+        );
+    }
+
+    #[test]
+    fn aarch64_scan_and_surgery() {
+        // bl 0x620; b 0x620; bl 0x2008; blr x8
+        let data = aarch64_words(&[0x97fffd88, 0x17fffd87, 0x94000400, 0xd63f0100]);
+        let sec = TextSection {
+            description: String::from(".text"),
+            data: Cow::Borrowed(&data[..]),
+            address: 0x1000,
+            file_offset: 0x800,
+            compressed: false,
+        };
+        let mut app_func_addresses = MutMap::default();
+        app_func_addresses.insert(0x620, "roc_foo");
+
+        let (entries, found_indirect) =
+            scan_text_section(SurgeryArch::AArch64, &app_func_addresses, &sec);
+
+        assert_eq!(
+            entries,
+            [
+                (
+                    "roc_foo",
+                    SurgeryEntry {
+                        file_offset: 0x800,
+                        virtual_offset: VirtualOffset::Relative(0x1000),
+                        size: 4,
+                    }
+                ),
+                (
+                    "roc_foo",
+                    SurgeryEntry {
+                        file_offset: 0x804,
+                        virtual_offset: VirtualOffset::Relative(0x1004),
+                        size: 4,
+                    }
+                ),
+            ]
+        );
+        assert!(found_indirect);
+
+        // Surgery points the branches at the app function, and keeps them a `bl` and a `b`.
+        let md = Metadata {
+            arch: SurgeryArch::AArch64,
+            added_byte_count: 0x1000,
+            ..Default::default()
+        };
+        let mut file = vec![0; 0x1800];
+        file.extend_from_slice(&data);
+        for (_, entry) in entries.iter() {
+            perform_surgery(&mut file, &md, entry, 0x9000, false);
+        }
+
+        let bl = aarch64::read(&file, 0x1800);
+        let b = aarch64::read(&file, 0x1804);
+        assert_eq!(aarch64::branch_target(bl, 0x2000), Some(0x9000));
+        assert_eq!(aarch64::branch_target(b, 0x2004), Some(0x9000));
+        assert_eq!(bl & 0xfc00_0000, 0x9400_0000);
+        assert_eq!(b & 0xfc00_0000, 0x1400_0000);
+    }
+
+    #[test]
+    fn aarch64_relocations() {
+        use aarch64::Relocation;
+
+        let relocate = |inst: u32, relocation, place, target| {
+            let mut data = inst.to_le_bytes();
+            aarch64::write_relocation(&mut data, 0, relocation, place, target);
+            u32::from_le_bytes(data)
+        };
+
+        // bl
+        assert_eq!(
+            relocate(0x94000000, Relocation::Branch26, 0x1000, 0x2000),
+            0x94000400
+        );
+        // adrp x0
+        let adrp = relocate(0x90000000, Relocation::Page21, 0x1234, 0x12345678);
+        assert_eq!(adrp, 0x90091a20);
+        assert_eq!(aarch64::adrp_page(adrp, 0x1234), Some((0, 0x12345000)));
+        // add x0, x0, #0
+        assert_eq!(
+            relocate(
+                0x91000000,
+                Relocation::Lo12 { shift: 0 },
+                0x1234,
+                0x12345678
+            ),
+            0x9119e000
+        );
+        // ldr x1, [x0]
+        assert_eq!(
+            relocate(
+                0xf9400001,
+                Relocation::Lo12 { shift: 3 },
+                0x1234,
+                0x12345670
+            ),
+            0xf9433801
+        );
+        // ldr x1, [x0] from a GOT slot becomes add x1, x0, #0x678
+        assert_eq!(
+            relocate(0xf9400001, Relocation::GotLo12, 0x1234, 0x12345678),
+            0x9119e001
+        );
+    }
+
+    #[test]
+    #[should_panic(expected = "does not fit in a 26 bit word offset")]
+    fn aarch64_branch_out_of_range() {
+        let mut data = 0x94000000u32.to_le_bytes();
+        aarch64::write_relocation(&mut data, 0, aarch64::Relocation::Branch26, 0, 0x1000_0000);
+    }
+
+    #[test]
+    fn aarch64_relocation_kinds() {
+        use object::write;
+
+        let mut app = write::Object::new(
+            object::BinaryFormat::Elf,
+            object::Architecture::Aarch64,
+            object::Endianness::Little,
+        );
+        let text = app.add_section(vec![], b".text".to_vec(), SectionKind::Text);
+        app.append_section_data(
+            text,
+            &aarch64_words(&[0x94000000, 0x90000000, 0x91000000]),
+            4,
+        );
+        let symbol = app.add_symbol(write::Symbol {
+            name: b"roc_foo".to_vec(),
+            value: 0,
+            size: 0,
+            kind: object::SymbolKind::Text,
+            scope: object::SymbolScope::Dynamic,
+            weak: false,
+            section: write::SymbolSection::Undefined,
+            flags: object::SymbolFlags::None,
+        });
+        for (offset, r_type) in [
+            (0, elf::R_AARCH64_CALL26),
+            (4, elf::R_AARCH64_ADR_PREL_PG_HI21),
+            (8, elf::R_AARCH64_ADD_ABS_LO12_NC),
+        ] {
+            app.add_relocation(
+                text,
+                write::Relocation {
+                    offset,
+                    size: 0,
+                    kind: RelocationKind::Elf(r_type),
+                    encoding: object::RelocationEncoding::Generic,
+                    symbol,
+                    addend: 0,
+                },
+            )
+            .unwrap();
+        }
+        let bytes = app.write().unwrap();
+
+        let app = object::File::parse(&*bytes).unwrap();
+        let relocations: Vec<_> = app
+            .section_by_name(".text")
+            .unwrap()
+            .relocations()
+            .map(|(_, relocation)| aarch64::Relocation::from_object(&relocation))
+            .collect();
+
+        assert_eq!(
+            relocations,
+            [
+                Some(aarch64::Relocation::Branch26),
+                Some(aarch64::Relocation::Page21),
+                Some(aarch64::Relocation::Lo12 { shift: 0 }),
+            ]
+        );
+    }
+
+    #[test]
+    fn aarch64_redirect_plt_stub() {
+        // bti c; adrp x16, 0x11000; ldr x17, [x16, #8]; add x16, x16, #8; br x17
+        let mut data = aarch64_words(&[0xd503245f, 0xb0000090, 0xf9400611, 0x91002210, 0xd61f0220]);
+        let plt_size = data.len();
+
+        redirect_plt_stub(
+            &mut data,
+            SurgeryArch::AArch64,
+            0,
+            0x630,
+            plt_size,
+            0x2000,
+            false,
+        );
+
+        assert_eq!(
+            data,
+            aarch64_words(&[0xd503245f, 0x14000673, 0xd503201f, 0xd503201f, 0xd503201f])
+        );
+    }
+
+    #[test]
+    #[should_panic(expected = "incompatible linker version")]
+    fn metadata_from_other_version() {
+        let dir = tempfile::tempdir().unwrap();
+        let metadata_path = dir.path().join("metadata");
+
+        let mut bytes =
+            bincode::serialize(&(Metadata::MAGIC, Metadata::FORMAT_VERSION + 1)).unwrap();
+        bytes.extend(bincode::serialize(&Metadata::default()).unwrap());
+        std::fs::write(&metadata_path, bytes).unwrap();
+
+        Metadata::read_from_file(&metadata_path);
+    }
+
+    #[test]
+    #[should_panic(expected = "is not a surgical linker metadata file")]
+    fn metadata_without_header() {
+        let dir = tempfile::tempdir().unwrap();
+        let metadata_path = dir.path().join("metadata");
+
+        std::fs::write(
+            &metadata_path,
+            bincode::serialize(&Metadata::default()).unwrap(),
+        )
+        .unwrap();
+
+        Metadata::read_from_file(&metadata_path);
+    }
+
+    #[test]
+    fn app_section_alignment() {
+        use object::write;
+
+        let mut app = write::Object::new(
+            object::BinaryFormat::Elf,
+            object::Architecture::X86_64,
+            object::Endianness::Little,
+        );
+        let text = app.add_section(vec![], b".text".to_vec(), SectionKind::Text);
+        app.append_section_data(text, &[0xc3], 0x10);
+        let simd = app.add_section(vec![], b".rodata.cst32".to_vec(), SectionKind::ReadOnlyData);
+        app.append_section_data(simd, &[0; 32], 0x100);
+        let bytes = app.write().unwrap();
+
+        let app = object::File::parse(&*bytes).unwrap();
+        let alignment = |name| section_alignment(&app.section_by_name(name).unwrap());
+
+        assert_eq!(alignment(".text"), MIN_SECTION_ALIGNMENT);
+        assert_eq!(alignment(".rodata.cst32"), 0x100);
+    }
+
+    #[test]
+    fn soname_versions() {
+        assert_eq!(soname_base("libapp.so"), "libapp.so");
+        assert_eq!(soname_base("libapp.so.1"), "libapp.so");
+        assert_eq!(soname_base("libapp.so.1.2.3"), "libapp.so");
+        assert_eq!(soname_base("libc.so.6"), "libc.so");
+        assert_ne!(soname_base("libapp2.so"), soname_base("libapp.so.2"));
+    }
+
+    #[test]
+    fn write_relocation_sizes() {
+        let mut data = [0u8; 16];
+        write_relocation(&mut data, 0, 32, true, -4);
+        write_relocation(&mut data, 4, 32, false, 0xffff_fffc);
+        write_relocation(&mut data, 8, 64, true, 0x1_0000_0000);
+        assert_eq!(data[..4], (-4i32).to_le_bytes());
+        assert_eq!(data[4..8], 0xffff_fffcu32.to_le_bytes());
+        assert_eq!(data[8..], 0x1_0000_0000i64.to_le_bytes());
+    }
+
+    #[test]
+    #[should_panic(expected = "does not fit in 32 bits")]
+    fn write_relocation_overflow() {
+        // An address above 4GiB cannot be stored in a 32 bit relocation.
+        let mut data = [0u8; 4];
+        write_relocation(&mut data, 0, 32, false, 0x1_0000_0000);
+    }
+
+    #[test]
+    #[should_panic(expected = "does not fit in 32 bits")]
+    fn write_relocation_signed_overflow() {
+        // An address above 2GiB would be sign extended into a negative one.
+        let mut data = [0u8; 4];
+        write_relocation(&mut data, 0, 32, true, 0x8000_0000);
+    }
+
+    #[test]
+    #[should_panic(expected = "does not fit in 32 bits")]
+    fn write_relocation_unsigned_negative() {
+        let mut data = [0u8; 4];
+        write_relocation(&mut data, 0, 32, false, -4);
+    }
+
+    #[test]
+    fn relax_rex_got_load() {
+        // mov rax, [rip + 0]
+        let mut data = [0x48, 0x8b, 0x05, 0, 0, 0, 0];
+        assert!(relax_got_load(&mut data, 3));
+        // lea rax, [rip + 0]
+        assert_eq!(data, [0x48, 0x8d, 0x05, 0, 0, 0, 0]);
+    }
+
+    #[test]
+    fn relax_got_load_keeps_other_instructions() {
+        // call [rip + 0]
+        let mut data = [0xff, 0x15, 0, 0, 0, 0];
+        assert!(!relax_got_load(&mut data, 2));
+        assert_eq!(data, [0xff, 0x15, 0, 0, 0, 0]);
+
+        // mov rax, [rbx], not RIP-relative
+        let mut data = [0x48, 0x8b, 0x03];
+        assert!(!relax_got_load(&mut data, 3));
+        assert_eq!(data, [0x48, 0x8b, 0x03]);
+    }
+
+    #[allow(dead_code)]
+    fn zig_host_app_help(dir: &Path, target: &Triple) {
+        let host_zig = indoc!(
+            r#"
+            const std = @import("std");
+
+            extern fn roc_magic1(usize) callconv(.C) [*]const u8;
+
+            pub fn main() !void {
+                const stdout = std.io.getStdOut().writer();
+                try stdout.print("Hello {s}\n", .{roc_magic1(0)[0..3]});
+            }
+            "#
+        );
+
+        let app_zig = indoc!(
+            r#"
+            const X = [_][]const u8 { "foo" };
+
+            export fn roc_magic1(index: usize) [*]const u8 {
+                return X[index].ptr;
+            }
+            "#
+        );
+
+        let zig = std::env::var("ROC_ZIG").unwrap_or_else(|_| "zig".into());
+
+        std::fs::write(dir.join("host.zig"), host_zig.as_bytes()).unwrap();
+        std::fs::write(dir.join("app.zig"), app_zig.as_bytes()).unwrap();
+
+        // we need to compile the app first
+        let output = std::process::Command::new(&zig)
+            .current_dir(dir)
+            .args(["build-obj", "app.zig", "-fPIC", "-OReleaseFast"])
+            .output()
+            .unwrap();
+
+        if !output.status.success() {
+            use std::io::Write;
+
+            std::io::stdout().write_all(&output.stdout).unwrap();
+            std::io::stderr().write_all(&output.stderr).unwrap();
+
+            panic!("zig build-obj failed");
+        }
+
+        // open our app object; we'll copy sections from it later
+        let file = std::fs::File::open(dir.join("app.o")).unwrap();
+        let roc_app = unsafe { memmap2::Mmap::map(&file) }.unwrap();
+
+        let names: Vec<String> = {
+            let object = object::File::parse(&*roc_app).unwrap();
+
+            object
+                .symbols()
+                .filter(|s| !s.is_local())
+                .map(|e| e.name().unwrap().to_string())
+                .collect()
+        };
+
+        let dylib_bytes =
+            crate::generate_dylib::create_dylib_elf64(&names, elf::EM_X86_64).unwrap();
         std::fs::write(dir.join("libapp.so"), dylib_bytes).unwrap();
 
         // now we can compile the host (it uses libapp.so, hence the order here)
@@ -1890,12 +3339,15 @@ mod tests {
         preprocess_elf(
             target_lexicon::Endianness::Little,
             &dir.join("host"),
-            &dir.join("metadata"),
+            Some(&dir.join("metadata")),
             &preprocessed_host_filename,
             &dir.join("libapp.so"),
+            &[],
             false,
             false,
-        );
+            false,
+        )
+        .unwrap();
 
         std::fs::copy(&preprocessed_host_filename, dir.join("final")).unwrap();
 
@@ -1903,9 +3355,12 @@ mod tests {
             &roc_app,
             &dir.join("metadata"),
             &dir.join("final"),
+            None,
             false,
             false,
-        );
+            false,
+        )
+        .unwrap();
     }
 
     #[cfg(target_os = "linux")]
@@ -1936,4 +3391,486 @@ mod tests {
 
         assert_eq!("Hello foo\n", output);
     }
+
+    #[cfg_attr(
+        not(all(target_os = "linux", target_arch = "x86_64")),
+        ignore = "surgical linking is only supported for x86_64 linux hosts"
+    )]
+    #[test]
+    fn c_host_app_strip_sections() {
+        let dir = tempfile::tempdir().unwrap();
+        let dir = dir.path();
+
+        let unstripped_len = std::fs::metadata(c_host_app_help(dir)).unwrap().len();
+
+        let roc_app = std::fs::read(dir.join("app.o")).unwrap();
+        let stripped_path = dir.join("stripped");
+        link_elf(
+            target_lexicon::Endianness::Little,
+            &dir.join("host"),
+            &dir.join("libapp.so"),
+            &[],
+            &roc_app,
+            &stripped_path,
+            None,
+            true,
+            false,
+            false,
+        )
+        .unwrap();
+
+        let bytes = std::fs::read(&stripped_path).unwrap();
+        let header = load_struct_inplace::<elf::FileHeader64<LE>>(&bytes, 0);
+        assert_eq!(header.e_shoff.get(LE), 0);
+        assert_eq!(header.e_shnum.get(LE), 0);
+        assert!((bytes.len() as u64) < unstripped_len);
+        assert_eq!(object::File::parse(&*bytes).unwrap().sections().count(), 0);
+
+        let output = std::process::Command::new(&stripped_path)
+            .current_dir(dir)
+            .output()
+            .unwrap();
+
+        assert_eq!(output.status.code(), Some(42));
+        assert_eq!("Hello foo\n", String::from_utf8_lossy(&output.stdout));
+    }
+
+    /// Builds the C app used by most surgery tests into `app.o`, and a C host that calls it
+    /// against a stub `libapp.so`. Returns the bytes of the app object.
+    fn c_host_help(dir: &Path) -> Vec<u8> {
+        let host_c = indoc!(
+            r#"
+            #include <stdio.h>
+
+            extern const char *roc_magic1(void);
+            extern int roc_magic2(int);
+
+            int main(void) {
+                printf("Hello %s\n", roc_magic1());
+                return roc_magic2(14);
+            }
+            "#
+        );
+
+        let app_c = indoc!(
+            r#"
+            const char *roc_magic1(void) { return "foo"; }
+
+            // Exported data is reached through a GOT load when compiled with -fPIC.
+            const int roc_factors[] = { 1, 2, 3, 4 };
+
+            int roc_magic2(int x) { return x * roc_factors[x % 4]; }
+            "#
+        );
+
+        c_host_help_with(dir, host_c, app_c)
+    }
+
+    /// Builds `app_c` into `app.o`, and `host_c` against a stub `libapp.so` that exports every
+    /// global symbol of the app. Returns the bytes of the app object.
+    fn c_host_help_with(dir: &Path, host_c: &str, app_c: &str) -> Vec<u8> {
+        std::fs::write(dir.join("app.c"), app_c.as_bytes()).unwrap();
+
+        run_cc(dir, &["-c", "app.c", "-fPIC", "-O2", "-o", "app.o"]);
+
+        let roc_app = std::fs::read(dir.join("app.o")).unwrap();
+
+        c_host_help_with_app(dir, host_c, &roc_app, &["-fPIE", "-pie"]);
+
+        roc_app
+    }
+
+    /// Builds `host_c` with the extra `host_args`, against a stub `libapp.so` that exports every
+    /// global symbol of `roc_app`.
+    fn c_host_help_with_app(dir: &Path, host_c: &str, roc_app: &[u8], host_args: &[&str]) {
+        std::fs::write(dir.join("host.c"), host_c.as_bytes()).unwrap();
+
+        let names: Vec<String> = {
+            let object = object::File::parse(roc_app).unwrap();
+
+            object
+                .symbols()
+                .filter(|s| !s.is_local())
+                .map(|e| e.name().unwrap().to_string())
+                .collect()
+        };
+
+        let dylib_bytes =
+            crate::generate_dylib::create_dylib_elf64(&names, elf::EM_X86_64).unwrap();
+        std::fs::write(dir.join("libapp.so"), dylib_bytes).unwrap();
+
+        let mut args = vec!["host.c", "libapp.so", "-O2", "-o", "host"];
+        args.extend_from_slice(host_args);
+        run_cc(dir, &args);
+    }
+
+    fn run_cc(dir: &Path, args: &[&str]) {
+        let cc = std::env::var("CC").unwrap_or_else(|_| "cc".into());
+
+        let output = std::process::Command::new(&cc)
+            .current_dir(dir)
+            .args(args)
+            .output()
+            .unwrap();
+
+        if !output.status.success() {
+            use std::io::Write;
+
+            std::io::stdout().write_all(&output.stdout).unwrap();
+            std::io::stderr().write_all(&output.stderr).unwrap();
+
+            panic!("{cc} {args:?} failed");
+        }
+    }
+
+    /// Builds a C host and app with `c_host_help`, then links the app into the host with
+    /// `link_elf`, without going through a metadata file. Returns the path of the linked executable.
+    fn c_host_app_help(dir: &Path) -> std::path::PathBuf {
+        let roc_app = c_host_help(dir);
+
+        link_elf(
+            target_lexicon::Endianness::Little,
+            &dir.join("host"),
+            &dir.join("libapp.so"),
+            &[],
+            &roc_app,
+            &dir.join("final"),
+            None,
+            false,
+            false,
+            false,
+        )
+        .unwrap();
+
+        dir.join("final")
+    }
+
+    #[cfg_attr(
+        not(all(target_os = "linux", target_arch = "x86_64")),
+        ignore = "surgical linking is only supported for x86_64 linux hosts"
+    )]
+    #[test]
+    fn c_host_app() {
+        let dir = tempfile::tempdir().unwrap();
+        let dir = dir.path();
+
+        let output = std::process::Command::new(c_host_app_help(dir))
+            .current_dir(dir)
+            .output()
+            .unwrap();
+
+        if output.status.code() != Some(42) {
+            use std::io::Write;
+
+            std::io::stdout().write_all(&output.stdout).unwrap();
+            std::io::stderr().write_all(&output.stderr).unwrap();
+
+            panic!("final exited with {}", output.status);
+        }
+
+        let output = String::from_utf8_lossy(&output.stdout);
+
+        assert_eq!("Hello foo\n", output);
+    }
+
+    #[cfg_attr(
+        not(all(target_os = "linux", target_arch = "x86_64")),
+        ignore = "surgical linking is only supported for x86_64 linux hosts"
+    )]
+    #[test]
+    fn c_host_app_relaxes_got_load() {
+        let dir = tempfile::tempdir().unwrap();
+        let dir = dir.path();
+
+        let final_path = c_host_app_help(dir);
+
+        let app_bytes = std::fs::read(dir.join("app.o")).unwrap();
+        let app_obj = object::File::parse(&*app_bytes).unwrap();
+        let has_got_load = app_obj
+            .sections()
+            .flat_map(|sec| sec.relocations().collect::<Vec<_>>())
+            .any(|(_, rel)| {
+                matches!(
+                    rel.kind(),
+                    RelocationKind::Elf(elf::R_X86_64_GOTPCRELX | elf::R_X86_64_REX_GOTPCRELX)
+                )
+            });
+        assert!(has_got_load, "app.o has no relaxable GOT load");
+
+        let output = std::process::Command::new(final_path)
+            .current_dir(dir)
+            .output()
+            .unwrap();
+
+        // 14 * roc_factors[2], read through the relaxed load.
+        assert_eq!(output.status.code(), Some(42));
+    }
+
+    #[cfg_attr(
+        not(all(target_os = "linux", target_arch = "x86_64")),
+        ignore = "surgical linking is only supported for x86_64 linux hosts"
+    )]
+    #[test]
+    fn dry_run_keeps_existing_metadata() {
+        let dir = tempfile::tempdir().unwrap();
+        let dir = dir.path();
+
+        c_host_help(dir);
+
+        let metadata_path = dir.join("metadata");
+        let old_metadata = b"metadata from an earlier preprocessing";
+        std::fs::write(&metadata_path, old_metadata).unwrap();
+
+        let md = preprocess_elf(
+            target_lexicon::Endianness::Little,
+            &dir.join("host"),
+            Some(&metadata_path),
+            &dir.join("preprocessedhost"),
+            &dir.join("libapp.so"),
+            &[],
+            true,
+            false,
+            false,
+        )
+        .unwrap();
+
+        assert!(!md.surgeries.is_empty());
+        assert_eq!(std::fs::read(&metadata_path).unwrap(), old_metadata);
+        assert!(!dir.join("preprocessedhost").exists());
+    }
+
+    #[cfg_attr(
+        not(all(target_os = "linux", target_arch = "x86_64")),
+        ignore = "surgical linking is only supported for x86_64 linux hosts"
+    )]
+    #[test]
+    fn c_host_app_with_allowlisted_app_function() {
+        let host_c = indoc!(
+            r#"
+            extern int magic_answer(void);
+
+            int main(void) {
+                return magic_answer();
+            }
+            "#
+        );
+
+        let app_c = indoc!(
+            r#"
+            int magic_answer(void) { return 42; }
+            "#
+        );
+
+        let dir = tempfile::tempdir().unwrap();
+        let dir = dir.path();
+
+        let roc_app = c_host_help_with(dir, host_c, app_c);
+
+        // `magic_answer` has no `roc_` prefix, so it is only an app function because it is listed.
+        link_elf(
+            target_lexicon::Endianness::Little,
+            &dir.join("host"),
+            &dir.join("libapp.so"),
+            &[String::from("magic_answer")],
+            &roc_app,
+            &dir.join("final"),
+            None,
+            false,
+            false,
+            false,
+        )
+        .unwrap();
+
+        let output = std::process::Command::new(dir.join("final"))
+            .current_dir(dir)
+            .output()
+            .unwrap();
+
+        assert_eq!(output.status.code(), Some(42));
+    }
+
+    #[cfg_attr(
+        not(all(target_os = "linux", target_arch = "x86_64")),
+        ignore = "surgical linking is only supported for x86_64 linux hosts"
+    )]
+    #[test]
+    fn c_host_app_size_budget() {
+        let dir = tempfile::tempdir().unwrap();
+        let dir = dir.path();
+
+        let final_path = c_host_app_help(dir);
+
+        // The linked executable ends with its section headers.
+        let section_headers_end = |path: &Path| {
+            let bytes = std::fs::read(path).unwrap();
+            let header = load_struct_inplace::<elf::FileHeader64<LE>>(&bytes, 0);
+            header.e_shoff.get(LE)
+                + header.e_shnum.get(LE) as u64 * header.e_shentsize.get(LE) as u64
+        };
+        let linked_size = section_headers_end(&final_path);
+
+        let roc_app = std::fs::read(dir.join("app.o")).unwrap();
+        let link_with_budget = |max_size| {
+            link_elf(
+                target_lexicon::Endianness::Little,
+                &dir.join("host"),
+                &dir.join("libapp.so"),
+                &[],
+                &roc_app,
+                &final_path,
+                Some(max_size),
+                false,
+                false,
+                false,
+            )
+        };
+
+        assert!(link_with_budget(linked_size).is_ok());
+        assert_eq!(section_headers_end(&final_path), linked_size);
+        assert_eq!(std::fs::metadata(&final_path).unwrap().len(), linked_size);
+
+        let err = link_with_budget(linked_size - 1).unwrap_err();
+        assert!(matches!(
+            err,
+            LinkError::SizeBudgetExceeded { size, max_size }
+                if size == linked_size && max_size == linked_size - 1
+        ));
+        assert!(
+            !final_path.exists(),
+            "an executable over the size budget was left behind"
+        );
+    }
+
+    #[cfg_attr(
+        not(all(target_os = "linux", target_arch = "x86_64")),
+        ignore = "surgical linking is only supported for x86_64 linux hosts"
+    )]
+    #[test]
+    fn c_host_app_missing_symbol() {
+        let host_c = indoc!(
+            r#"
+            extern int roc_magic(void);
+
+            int main(void) {
+                return roc_magic();
+            }
+            "#
+        );
+
+        let app_c = indoc!(
+            r#"
+            int roc_magic(void) { return 42; }
+            "#
+        );
+
+        let dir = tempfile::tempdir().unwrap();
+        let dir = dir.path();
+
+        c_host_help_with(dir, host_c, app_c);
+
+        // An app that does not define `roc_magic`, which the host calls.
+        std::fs::write(dir.join("other.c"), "int roc_other(void) { return 1; }\n").unwrap();
+        run_cc(dir, &["-c", "other.c", "-fPIC", "-O2", "-o", "other.o"]);
+        let other_app = std::fs::read(dir.join("other.o")).unwrap();
+
+        let err = link_elf(
+            target_lexicon::Endianness::Little,
+            &dir.join("host"),
+            &dir.join("libapp.so"),
+            &[],
+            &other_app,
+            &dir.join("final"),
+            None,
+            false,
+            false,
+            false,
+        )
+        .unwrap_err();
+
+        assert!(matches!(err, LinkError::MissingSymbol(name) if name == "roc_magic"));
+        assert!(!dir.join("final").exists());
+    }
+
+    #[cfg_attr(
+        not(all(target_os = "linux", target_arch = "x86_64")),
+        ignore = "surgical linking is only supported for x86_64 linux hosts"
+    )]
+    #[test]
+    #[should_panic(expected = "does not fit in 32 bits")]
+    fn c_host_app_absolute_relocation_overflow() {
+        use object::write;
+
+        let host_c = indoc!(
+            r#"
+            extern int roc_magic(void);
+
+            int main(void) { return roc_magic(); }
+            "#
+        );
+
+        // `roc_magic` loads the address of `roc_value` + 4GiB with an `R_X86_64_32`, which can
+        // not hold it.
+        let mut app = write::Object::new(
+            object::BinaryFormat::Elf,
+            object::Architecture::X86_64,
+            object::Endianness::Little,
+        );
+        let text = app.add_section(vec![], b".text".to_vec(), SectionKind::Text);
+        let rodata = app.add_section(vec![], b".rodata".to_vec(), SectionKind::ReadOnlyData);
+        // mov eax, imm32; ret
+        let magic_offset = app.append_section_data(text, &[0xb8, 0, 0, 0, 0, 0xc3], 16);
+        let value_offset = app.append_section_data(rodata, &42i32.to_le_bytes(), 4);
+        app.add_symbol(write::Symbol {
+            name: b"roc_magic".to_vec(),
+            value: magic_offset,
+            size: 6,
+            kind: object::SymbolKind::Text,
+            scope: object::SymbolScope::Dynamic,
+            weak: false,
+            section: write::SymbolSection::Section(text),
+            flags: object::SymbolFlags::None,
+        });
+        let value = app.add_symbol(write::Symbol {
+            name: b"roc_value".to_vec(),
+            value: value_offset,
+            size: 4,
+            kind: object::SymbolKind::Data,
+            scope: object::SymbolScope::Compilation,
+            weak: false,
+            section: write::SymbolSection::Section(rodata),
+            flags: object::SymbolFlags::None,
+        });
+        app.add_relocation(
+            text,
+            write::Relocation {
+                offset: magic_offset + 1,
+                size: 32,
+                kind: RelocationKind::Absolute,
+                encoding: RelocationEncoding::Generic,
+                symbol: value,
+                addend: 0x1_0000_0000,
+            },
+        )
+        .unwrap();
+        let roc_app = app.write().unwrap();
+
+        let dir = tempfile::tempdir().unwrap();
+        let dir = dir.path();
+
+        // Absolute relocations are only resolved for hosts that are not position-independent.
+        c_host_help_with_app(dir, host_c, &roc_app, &["-no-pie"]);
+
+        let _ = link_elf(
+            target_lexicon::Endianness::Little,
+            &dir.join("host"),
+            &dir.join("libapp.so"),
+            &[],
+            &roc_app,
+            &dir.join("final"),
+            None,
+            false,
+            false,
+            false,
+        );
+    }
 }
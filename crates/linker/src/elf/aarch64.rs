@@ -0,0 +1,171 @@
+//! Decoding and patching of the few AArch64 instructions that surgical linking touches.
+//! Every AArch64 instruction is a little endian 32 bit word.
+use object::{elf, RelocationEncoding, RelocationKind};
+use roc_error_macros::internal_error;
+
+/// `bti c`, the landing pad for indirect calls.
+pub(super) const BTI_C: u32 = 0xd503_245f;
+pub(super) const NOP: u32 = 0xd503_201f;
+/// `b` with an offset of zero.
+pub(super) const B: u32 = 0x1400_0000;
+
+const BRANCH_MASK: u32 = 0x7c00_0000;
+const BRANCH_IMM_MASK: u32 = 0x03ff_ffff;
+const ADRP_MASK: u32 = 0x9f00_0000;
+const ADRP: u32 = 0x9000_0000;
+const LDR64_IMM_MASK: u32 = 0xffc0_0000;
+const LDR64_IMM: u32 = 0xf940_0000;
+const ADD64_IMM: u32 = 0x9100_0000;
+const INDIRECT_BRANCH_MASK: u32 = 0xffff_fc1f;
+const BR: u32 = 0xd61f_0000;
+const BLR: u32 = 0xd63f_0000;
+
+pub(super) fn read(data: &[u8], offset: usize) -> u32 {
+    u32::from_le_bytes(data[offset..][..4].try_into().unwrap())
+}
+
+pub(super) fn write(data: &mut [u8], offset: usize, inst: u32) {
+    data[offset..][..4].copy_from_slice(&inst.to_le_bytes());
+}
+
+fn sign_extend(value: u32, bits: u32) -> i64 {
+    ((value << (32 - bits)) as i32 >> (32 - bits)) as i64
+}
+
+/// The target of a `b` or `bl` at `address`.
+pub(super) fn branch_target(inst: u32, address: u64) -> Option<u64> {
+    if inst & BRANCH_MASK == B {
+        let offset = sign_extend(inst & BRANCH_IMM_MASK, 26) * 4;
+        Some(address.wrapping_add(offset as u64))
+    } else {
+        None
+    }
+}
+
+/// Whether `inst` is a `br` or `blr`, whose target we can not know.
+pub(super) fn is_indirect_branch(inst: u32) -> bool {
+    matches!(inst & INDIRECT_BRANCH_MASK, BR | BLR)
+}
+
+/// The destination register and the page that an `adrp` at `address` computes.
+pub(super) fn adrp_page(inst: u32, address: u64) -> Option<(u32, u64)> {
+    if inst & ADRP_MASK == ADRP {
+        let immlo = (inst >> 29) & 0b11;
+        let immhi = (inst >> 5) & 0x7_ffff;
+        let offset = sign_extend((immhi << 2) | immlo, 21) << 12;
+        Some((inst & 0x1f, (address & !0xfff).wrapping_add(offset as u64)))
+    } else {
+        None
+    }
+}
+
+/// The destination register, base register and byte offset of an `ldr xt, [xn, #offset]`.
+pub(super) fn ldr64_offset(inst: u32) -> Option<(u32, u32, u64)> {
+    if inst & LDR64_IMM_MASK == LDR64_IMM {
+        let offset = ((inst >> 10) & 0xfff) as u64 * 8;
+        Some((inst & 0x1f, (inst >> 5) & 0x1f, offset))
+    } else {
+        None
+    }
+}
+
+/// How an AArch64 relocation patches the instruction at its location.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(super) enum Relocation {
+    /// The 26 bit word offset of a `b` or `bl`, `S + A - P`.
+    Branch26,
+    /// The 21 bit page offset of an `adrp`, `Page(S + A) - Page(P)`.
+    Page21,
+    /// The low 12 bits of `S + A` in an `add`, or in a load or store whose offset is scaled
+    /// by `1 << shift`.
+    Lo12 { shift: u32 },
+    /// The low 12 bits of the GOT slot of `S` in an `ldr`. We do not build a GOT for the app,
+    /// so the load is rewritten into an `add` of the low 12 bits of `S + A` instead.
+    GotLo12,
+}
+
+impl Relocation {
+    /// The relocation the app object asks for, if it is one that surgery supports.
+    pub(super) fn from_object(relocation: &object::Relocation) -> Option<Self> {
+        match (relocation.kind(), relocation.encoding()) {
+            (RelocationKind::PltRelative, RelocationEncoding::AArch64Call) => {
+                Some(Relocation::Branch26)
+            }
+            (RelocationKind::Elf(r_type), _) => match r_type {
+                elf::R_AARCH64_CALL26 | elf::R_AARCH64_JUMP26 => Some(Relocation::Branch26),
+                // A GOT page is the page of the symbol itself once the load is relaxed.
+                elf::R_AARCH64_ADR_PREL_PG_HI21 | elf::R_AARCH64_ADR_GOT_PAGE => {
+                    Some(Relocation::Page21)
+                }
+                elf::R_AARCH64_ADD_ABS_LO12_NC | elf::R_AARCH64_LDST8_ABS_LO12_NC => {
+                    Some(Relocation::Lo12 { shift: 0 })
+                }
+                elf::R_AARCH64_LDST16_ABS_LO12_NC => Some(Relocation::Lo12 { shift: 1 }),
+                elf::R_AARCH64_LDST32_ABS_LO12_NC => Some(Relocation::Lo12 { shift: 2 }),
+                elf::R_AARCH64_LDST64_ABS_LO12_NC => Some(Relocation::Lo12 { shift: 3 }),
+                elf::R_AARCH64_LDST128_ABS_LO12_NC => Some(Relocation::Lo12 { shift: 4 }),
+                elf::R_AARCH64_LD64_GOT_LO12_NC => Some(Relocation::GotLo12),
+                _ => None,
+            },
+            _ => None,
+        }
+    }
+}
+
+/// Patches the instruction at `base`, which is at the virtual address `place`, so that it
+/// refers to `target`. A target that does not fit in the instruction is an error rather than
+/// silently truncated.
+pub(super) fn write_relocation(
+    data: &mut [u8],
+    base: usize,
+    relocation: Relocation,
+    place: u64,
+    target: u64,
+) {
+    let inst = read(data, base);
+    let patched = match relocation {
+        Relocation::Branch26 => {
+            let offset = target.wrapping_sub(place) as i64;
+            if offset % 4 != 0 || !(-(1 << 27)..(1 << 27)).contains(&offset) {
+                internal_error!(
+                    "Branch from {place:+x} to {target:+x} does not fit in a 26 bit word offset"
+                );
+            }
+            (inst & !BRANCH_IMM_MASK) | ((offset >> 2) as u32 & BRANCH_IMM_MASK)
+        }
+        Relocation::Page21 => {
+            let pages = ((target & !0xfff) as i64 - (place & !0xfff) as i64) >> 12;
+            if !(-(1 << 20)..(1 << 20)).contains(&pages) {
+                internal_error!(
+                    "Page of {target:+x} is too far from {place:+x} for a 21 bit page offset"
+                );
+            }
+            let pages = pages as u32;
+            (inst & !((0b11 << 29) | (0x7_ffff << 5)))
+                | ((pages & 0b11) << 29)
+                | (((pages >> 2) & 0x7_ffff) << 5)
+        }
+        Relocation::Lo12 { shift } => {
+            let low = (target & 0xfff) as u32;
+            if low % (1 << shift) != 0 {
+                internal_error!(
+                    "Target {target:+x} is not aligned to the {} bytes that its load or store needs",
+                    1 << shift
+                );
+            }
+            (inst & !(0xfff << 10)) | ((low >> shift) << 10)
+        }
+        Relocation::GotLo12 => {
+            if inst & LDR64_IMM_MASK != LDR64_IMM {
+                internal_error!(
+                    "GOT load can not be relaxed, only `ldr xt, [xn, #offset]` is supported: {inst:#010x}"
+                );
+            }
+            // Keep the registers, and replace the load with `add xt, xn, #lo12`.
+            let low = (target & 0xfff) as u32;
+            ADD64_IMM | (low << 10) | (inst & 0x3ff)
+        }
+    };
+
+    write(data, base, patched);
+}
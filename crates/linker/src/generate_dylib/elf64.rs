@@ -2,7 +2,10 @@ use object::{elf, Endianness};
 
 use crate::pe::next_multiple_of;
 
-pub fn create_dylib_elf64(custom_names: &[String]) -> object::read::Result<Vec<u8>> {
+pub fn create_dylib_elf64(
+    custom_names: &[String],
+    e_machine: u16,
+) -> object::read::Result<Vec<u8>> {
     let endian = Endianness::Little;
 
     let mut out_data = Vec::new();
@@ -145,7 +148,7 @@ pub fn create_dylib_elf64(custom_names: &[String]) -> object::read::Result<Vec<u
             os_abi: 0,
             abi_version: 0,
             e_type: 3,
-            e_machine: 62,
+            e_machine,
             e_entry: 0x1000,
             e_flags: 0,
         })
@@ -5,7 +5,7 @@
 //! practical to use a regular linker.
 use memmap2::{Mmap, MmapMut};
 use object::Object;
-use roc_error_macros::internal_error;
+use roc_error_macros::{internal_error, user_error};
 use roc_load::{EntryPoint, ExecutionMode, ExposedToHost, LoadConfig, Threading};
 use roc_module::symbol::Interns;
 use roc_packaging::cache::RocCacheDir;
@@ -62,6 +62,76 @@ pub fn supported(link_type: LinkType, target: &Triple) -> bool {
     }
 }
 
+/// Whether the surgical linker can be used for `target` when `--linker surgical` asks for it.
+/// Besides the `supported` targets, this includes AArch64 Linux, which is not the default yet.
+pub fn supported_when_requested(link_type: LinkType, target: &Triple) -> bool {
+    supported(link_type, target)
+        || matches!(
+            (link_type, target),
+            (
+                LinkType::Executable,
+                Triple {
+                    architecture: target_lexicon::Architecture::Aarch64(_),
+                    operating_system: target_lexicon::OperatingSystem::Linux,
+                    binary_format: target_lexicon::BinaryFormat::Elf,
+                    ..
+                }
+            )
+        )
+}
+
+/// Why linking an app into a host failed.
+#[derive(Debug)]
+pub enum LinkError {
+    /// The linked executable is over the `--max-size` budget.
+    SizeBudgetExceeded {
+        size: u64,
+        max_size: u64,
+    },
+    /// The host targets an architecture that surgical linking does not support.
+    UnsupportedArch(object::Architecture),
+    /// The host calls a function that the app does not define.
+    MissingSymbol(String),
+    Io(std::io::Error),
+}
+
+impl std::fmt::Display for LinkError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            LinkError::SizeBudgetExceeded { size, max_size } => write!(
+                f,
+                "The linked executable is {size} bytes, which is over the size budget of {max_size} bytes."
+            ),
+            LinkError::UnsupportedArch(arch) => write!(
+                f,
+                "Surgical linking does not support {arch:?} ELF hosts yet.\nPlease use `--linker=legacy` instead."
+            ),
+            LinkError::MissingSymbol(name) => write!(
+                f,
+                concat!(
+                    "Function, {}, was not defined by the app.\n",
+                    "\nPotential causes:\n",
+                    "\n\t- because the platform was built with a non-compatible version of roc compared to the one you are running.\n",
+                    "\n\t\tsolutions:\n",
+                    "\t\t\t+ Downgrade your roc version to the one that was used to build the platform.\n",
+                    "\t\t\t+ Or ask the platform author to release a new version of the platform using a current roc release.\n",
+                    "\n\t- This can also occur due to a bug in the compiler. In that case, file an issue here: https://github.com/roc-lang/roc/issues/new/choose"
+                ),
+                name
+            ),
+            LinkError::Io(err) => write!(f, "{err}"),
+        }
+    }
+}
+
+impl std::error::Error for LinkError {}
+
+impl From<std::io::Error> for LinkError {
+    fn from(err: std::io::Error) -> Self {
+        LinkError::Io(err)
+    }
+}
+
 pub const PRECOMPILED_HOST_EXT: &str = "rh"; // Short for "roc host"
 
 pub fn preprocessed_host_filename(target: &Triple) -> Option<String> {
@@ -74,14 +144,100 @@ fn metadata_file_name(target: &Triple) -> String {
     format!("metadata_{}.rm", target_triple_str.unwrap_or("unknown"))
 }
 
+/// Links the app in place into the copy of the preprocessed host at `binary_path`.
+/// With `strip_sections`, the section header table is left out of the output.
+/// Fails if the host calls a function the app does not define, or if the linked executable
+/// is over `max_size` bytes.
 pub fn link_preprocessed_host(
     target: &Triple,
     platform_path: &Path,
     roc_app_bytes: &[u8],
     binary_path: &Path,
-) {
+    max_size: Option<u64>,
+    strip_sections: bool,
+) -> Result<(), LinkError> {
     let metadata = platform_path.with_file_name(metadata_file_name(target));
-    surgery(roc_app_bytes, &metadata, binary_path, false, false, target)
+    surgery(
+        roc_app_bytes,
+        &metadata,
+        binary_path,
+        max_size,
+        strip_sections,
+        false,
+        false,
+        target,
+    )
+}
+
+/// Preprocesses the host and links the app into it in a single step, writing the result to
+/// `output_path`. Only ELF keeps the metadata in memory; Mach-O and PE still write it to a
+/// temporary file and read it back, since their surgery only works from a metadata file.
+/// `app_function_names` lists host imports without the `roc_` prefix that the app provides.
+/// With `strip_sections`, the section header table is left out of the output.
+/// Fails if the host executable can not be read, if its architecture is not supported, if the
+/// metadata file can not be created on formats that still need it, if the host calls a function
+/// the app does not define, or if the linked executable is over `max_size` bytes.
+#[allow(clippy::too_many_arguments)]
+pub fn link(
+    target: &Triple,
+    host_exe_path: &Path,
+    shared_lib: &Path,
+    stub_dll_symbols: &[String],
+    app_function_names: &[String],
+    roc_app_bytes: &[u8],
+    output_path: &Path,
+    max_size: Option<u64>,
+    strip_sections: bool,
+) -> Result<(), LinkError> {
+    std::fs::metadata(host_exe_path)?;
+
+    match target.binary_format {
+        target_lexicon::BinaryFormat::Elf => {
+            let endianness = target
+                .endianness()
+                .unwrap_or(target_lexicon::Endianness::Little);
+
+            crate::elf::link_elf(
+                endianness,
+                host_exe_path,
+                shared_lib,
+                app_function_names,
+                roc_app_bytes,
+                output_path,
+                max_size,
+                strip_sections,
+                false,
+                false,
+            )
+        }
+        _ => {
+            let metadata_dir = tempfile::tempdir()?;
+            let metadata_path = metadata_dir.path().join(metadata_file_name(target));
+
+            preprocess(
+                target,
+                host_exe_path,
+                &metadata_path,
+                output_path,
+                shared_lib,
+                stub_dll_symbols,
+                app_function_names,
+                false,
+                false,
+                false,
+            );
+            surgery(
+                roc_app_bytes,
+                &metadata_path,
+                output_path,
+                max_size,
+                strip_sections,
+                false,
+                false,
+                target,
+            )
+        }
+    }
 }
 
 // Exposed function to load a platform file and generate a stub lib for it.
@@ -367,7 +523,10 @@ fn stub_lib_is_up_to_date(target: &Triple, stub_lib_path: &Path, custom_names: &
     }
 
     let stub_lib = open_mmap(stub_lib_path);
-    let object = object::File::parse(&*stub_lib).unwrap();
+    let object = match object::File::parse(&*stub_lib) {
+        Ok(object) => object,
+        Err(_) => return false,
+    };
 
     // the user may have been cross-compiling.
     // The dynhost on disk must match our current target
@@ -377,7 +536,11 @@ fn stub_lib_is_up_to_date(target: &Triple, stub_lib_path: &Path, custom_names: &
 
     // we made this dynhost file. For the file to be the same as what we'd generate,
     // we need all symbols to be there and in the correct order
-    let dynamic_symbols: Vec<_> = object.exports().unwrap();
+    // (a file without an export table was not made by us either)
+    let dynamic_symbols = match object.exports() {
+        Ok(exports) => exports,
+        Err(_) => return false,
+    };
 
     let it1 = dynamic_symbols.iter().map(|e| e.name());
     let it2 = custom_names.iter().map(|s| s.as_bytes());
@@ -385,12 +548,17 @@ fn stub_lib_is_up_to_date(target: &Triple, stub_lib_path: &Path, custom_names: &
     it1.eq(it2)
 }
 
+/// With `dry_run`, the host is only analyzed. Neither the preprocessed host nor its metadata is written.
+/// `app_function_names` lists host imports without the `roc_` prefix that the app provides,
+/// for platforms that export the Roc entry points under other names. Only ELF hosts use it.
 pub fn preprocess_host(
     target: &Triple,
     platform_main_roc: &Path,
     preprocessed_path: &Path,
     shared_lib: &Path,
     stub_dll_symbols: &[String],
+    app_function_names: &[String],
+    dry_run: bool,
 ) {
     let metadata_path = platform_main_roc.with_file_name(metadata_file_name(target));
     let host_exe_path = if let target_lexicon::OperatingSystem::Windows = target.operating_system {
@@ -406,6 +574,8 @@ pub fn preprocess_host(
         preprocessed_path,
         shared_lib,
         stub_dll_symbols,
+        app_function_names,
+        dry_run,
         false,
         false,
     )
@@ -420,6 +590,8 @@ fn preprocess(
     preprocessed_path: &Path,
     shared_lib: &Path,
     stub_dll_symbols: &[String],
+    app_function_names: &[String],
+    dry_run: bool,
     verbose: bool,
     time: bool,
 ) {
@@ -427,6 +599,12 @@ fn preprocess(
         println!("Targeting: {target}");
     }
 
+    if dry_run && target.binary_format != target_lexicon::BinaryFormat::Elf {
+        user_error!(
+            "A dry run of the surgical linker preprocessor is only supported for ELF hosts"
+        );
+    }
+
     let endianness = target
         .endianness()
         .unwrap_or(target_lexicon::Endianness::Little);
@@ -436,12 +614,15 @@ fn preprocess(
             crate::elf::preprocess_elf(
                 endianness,
                 host_exe_path,
-                metadata_path,
+                Some(metadata_path),
                 preprocessed_path,
                 shared_lib,
+                app_function_names,
+                dry_run,
                 verbose,
                 time,
-            );
+            )
+            .unwrap_or_else(|e| user_error!("{}", e));
         }
 
         target_lexicon::BinaryFormat::Macho => {
@@ -486,18 +667,31 @@ fn preprocess(
     }
 }
 
+#[allow(clippy::too_many_arguments)]
 fn surgery(
     roc_app_bytes: &[u8],
     metadata_path: &Path,
     executable_path: &Path,
+    max_size: Option<u64>,
+    strip_sections: bool,
     verbose: bool,
     time: bool,
     target: &Triple,
-) {
+) -> Result<(), LinkError> {
+    if strip_sections && target.binary_format != target_lexicon::BinaryFormat::Elf {
+        user_error!("Stripping the section headers is only supported for ELF hosts");
+    }
+
     match target.binary_format {
-        target_lexicon::BinaryFormat::Elf => {
-            crate::elf::surgery_elf(roc_app_bytes, metadata_path, executable_path, verbose, time);
-        }
+        target_lexicon::BinaryFormat::Elf => crate::elf::surgery_elf(
+            roc_app_bytes,
+            metadata_path,
+            executable_path,
+            max_size,
+            strip_sections,
+            verbose,
+            time,
+        ),
 
         target_lexicon::BinaryFormat::Macho => {
             crate::macho::surgery_macho(
@@ -507,10 +701,14 @@ fn surgery(
                 verbose,
                 time,
             );
+
+            check_executable_size_budget(executable_path, max_size)
         }
 
         target_lexicon::BinaryFormat::Coff => {
             crate::pe::surgery_pe(executable_path, metadata_path, roc_app_bytes);
+
+            check_executable_size_budget(executable_path, max_size)
         }
 
         target_lexicon::BinaryFormat::Wasm => {
@@ -531,6 +729,29 @@ fn surgery(
     }
 }
 
+/// Fails if an output of `size` bytes is over the `max_size` budget, when there is one.
+pub(crate) fn check_size_budget(size: u64, max_size: Option<u64>) -> Result<(), LinkError> {
+    match max_size {
+        Some(max_size) if size > max_size => Err(LinkError::SizeBudgetExceeded { size, max_size }),
+        _ => Ok(()),
+    }
+}
+
+/// Checks the size of the executable at `executable_path` against the `max_size` budget.
+/// An executable that is over budget is removed, so no oversized output is left behind.
+fn check_executable_size_budget(
+    executable_path: &Path,
+    max_size: Option<u64>,
+) -> Result<(), LinkError> {
+    let result = check_size_budget(std::fs::metadata(executable_path)?.len(), max_size);
+
+    if result.is_err() {
+        std::fs::remove_file(executable_path)?;
+    }
+
+    result
+}
+
 pub(crate) fn align_by_constraint(offset: usize, constraint: usize) -> usize {
     if offset % constraint == 0 {
         offset
@@ -133,7 +133,7 @@ impl PeMetadata {
 
         let exports: MutMap<String, i64> = dynhost_obj
             .exports()
-            .unwrap()
+            .unwrap_or_else(|err| internal_error!("Failed to read the host exports: {}", err))
             .into_iter()
             .map(|e| {
                 (
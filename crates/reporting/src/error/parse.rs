@@ -1514,10 +1514,19 @@ fn to_unfinished_if_report<'a>(
     start: Position,
     message: RocDocBuilder<'a>,
 ) -> Report<'a> {
-    let surroundings = Region::new(start, pos);
-    let region = LineColumnRegion::from_pos(lines.convert_pos(pos));
+    let misspelled = next_misspelled_keyword(alloc.src_lines, lines, pos);
+    let (surroundings, region) = match misspelled {
+        Some((word_region, _)) => (
+            Region::new(start, word_region.end()),
+            lines.convert_region(word_region),
+        ),
+        None => (
+            Region::new(start, pos),
+            LineColumnRegion::from_pos(lines.convert_pos(pos)),
+        ),
+    };
 
-    let doc = alloc.stack([
+    let mut stack = vec![
         alloc.concat([
             alloc.reflow(r"I was partway through parsing an "),
             alloc.keyword("if"),
@@ -1525,7 +1534,10 @@ fn to_unfinished_if_report<'a>(
         ]),
         alloc.region_with_subregion(lines.convert_region(surroundings), region),
         message,
-    ]);
+    ];
+    stack.extend(misspelled.map(|(_, keyword)| did_you_mean_keyword(alloc, keyword)));
+
+    let doc = alloc.stack(stack);
 
     Report {
         filename,
@@ -4099,6 +4111,49 @@ fn what_is_next<'a>(source_lines: &'a [&'a str], pos: LineColumn) -> Next<'a> {
     }
 }
 
+/// The first word at or after `pos`, if it looks like a misspelled keyword, with its region
+/// and the keyword it was probably meant to be.
+fn next_misspelled_keyword(
+    source_lines: &[&str],
+    lines: &LineInfo,
+    pos: Position,
+) -> Option<(Region, &'static str)> {
+    let start = lines.convert_pos(pos);
+
+    for (row, line) in source_lines.iter().enumerate().skip(start.line as usize) {
+        let column = if row == start.line as usize {
+            start.column as usize
+        } else {
+            0
+        };
+        let rest = line.get(column..).unwrap_or_default();
+        let word = rest.trim_start_matches(' ');
+
+        if !word.is_empty() {
+            let word_start = lines.convert_line_column(LineColumn {
+                line: row as u32,
+                column: (column + rest.len() - word.len()) as u32,
+            });
+
+            return roc_parse::ident::misspelled_keyword(word.as_bytes(), word_start);
+        }
+    }
+
+    None
+}
+
+fn did_you_mean_keyword<'a>(
+    alloc: &'a RocDocAllocator<'a>,
+    keyword: &'static str,
+) -> RocDocBuilder<'a> {
+    alloc.concat([
+        alloc.tip(),
+        alloc.reflow(r"Did you mean the "),
+        alloc.keyword(keyword),
+        alloc.reflow(r" keyword?"),
+    ])
+}
+
 pub fn starts_with_keyword(rest_of_line: &str, keyword: &str) -> bool {
     if let Some(stripped) = rest_of_line.strip_prefix(keyword) {
         match stripped.chars().next() {